@@ -0,0 +1,144 @@
+use crate::config;
+use crate::global::escape_slashes;
+use crate::lexer::{self, TokenKind};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+// Aliases currently known to the shell. They start as a copy of aliases from the config file.
+static ALIASES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+fn aliases() -> MutexGuard<'static, BTreeMap<String, String>> {
+    ALIASES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn init(list: HashMap<String, String>) {
+    *aliases() = list.into_iter().collect();
+}
+
+pub fn names() -> Vec<String> {
+    aliases().keys().cloned().collect()
+}
+
+/*
+Replace the first word of a command with contents of an alias.
+Alias can point to another alias, so this is repeated until there is nothing to replace.
+Every alias is expanded only once, so aliases referring to themselves are fine:
+alias ls "ls --color"
+*/
+pub fn expand(mut args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut used: Vec<String> = Vec::new();
+    // Command without any words has nothing to replace.
+    // List of aliases is not locked while the alias is expanded, because "$(...)" inside may use aliases too.
+    while let Some(value) = args.first().and_then(|x| aliases().get(x).cloned()) {
+        if used.contains(&args[0]) {
+            break;
+        }
+        used.push(args[0].clone());
+        let mut words = split(&value)?;
+        words.extend(args.drain(1..));
+        args = words;
+    }
+    Ok(args)
+}
+
+// Turn contents of an alias into words, just like the shell would do with a typed command
+fn split(value: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    for w in raw_words(value)? {
+        words.push(escape_slashes(&w, true, true)?);
+    }
+    Ok(words)
+}
+
+// Words of an alias before variables and "$(...)" are resolved. This is enough to check if the alias is fine.
+fn raw_words(value: &str) -> Result<Vec<String>, String> {
+    let tokens = lexer::tokenize(value).map_err(|e| format!("Alias is broken: {e}"))?;
+    let mut words = Vec::new();
+    for t in tokens {
+        match t.kind {
+            TokenKind::Word(w) if w != "|" => words.push(w),
+            _ => return Err("Alias can only contain a single command".to_string()),
+        }
+    }
+    if words.is_empty() {
+        return Err("Alias can't be empty".to_string());
+    }
+    Ok(words)
+}
+
+// "-s" or "--save" as the first argument writes changes to the config file too
+fn wants_saving(buf: &[String]) -> (bool, &[String]) {
+    match buf.get(1).map(String::as_str) {
+        Some("-s") | Some("--save") => (true, &buf[2..]),
+        _ => (false, &buf[1..]),
+    }
+}
+
+/*
+alias                       -- Show all aliases
+alias ll                    -- Show what "ll" means
+alias ll ls -l              -- Make "ll" run "ls -l"
+alias -s ll ls -l           -- Same, but also remember it in the config file
+*/
+pub fn alias(buf: &[String]) -> Result<(), String> {
+    let (save, buf) = wants_saving(buf);
+    let mut aliases = aliases();
+
+    match buf.len() {
+        0 => {
+            for (name, value) in aliases.iter() {
+                println!("{name} = {value}");
+            }
+            Ok(())
+        }
+        1 => match aliases.get(&buf[0]) {
+            Some(value) => {
+                println!("{} = {value}", buf[0]);
+                Ok(())
+            }
+            None => Err(format!("Alias \"{}\" is not set!", buf[0])),
+        },
+        _ => {
+            let name = &buf[0];
+            if !name
+                .chars()
+                .all(|x| x.is_alphanumeric() || x == '_' || x == '-' || x == '.')
+            {
+                return Err(format!("Alias name \"{name}\" contains illegal characters!"));
+            }
+            let value = buf[1..].join(" ");
+            // Variables and commands inside are resolved when the alias is used, not now
+            raw_words(&value)?;
+            aliases.insert(name.clone(), value.clone());
+            if save {
+                let mut cfg = config::load();
+                cfg.aliases.insert(name.clone(), value);
+                config::save(cfg)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// unalias [-s] NAME...
+pub fn unalias(buf: &[String]) -> Result<(), String> {
+    let (save, buf) = wants_saving(buf);
+    if buf.is_empty() {
+        return Err("Give me an alias name to remove!".to_string());
+    }
+    let mut aliases = aliases();
+
+    for name in buf {
+        if aliases.remove(name).is_none() {
+            return Err(format!("Alias \"{name}\" is not set!"));
+        }
+    }
+    if save {
+        let mut cfg = config::load();
+        for name in buf {
+            cfg.aliases.remove(name);
+        }
+        config::save(cfg)?;
+    }
+    Ok(())
+}
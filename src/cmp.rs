@@ -1,14 +1,18 @@
 use std::process;
 use carrot_libs::args;
-use num_bigint::BigInt;
-mod helpful;
-use crate::helpful::*;
+// Only some parts of these files from rush are needed here
+#[allow(dead_code)]
+mod glob;
+#[allow(dead_code)]
+mod number;
+use crate::number::Number;
+use regex::RegexBuilder;
 
 #[allow(clippy::needless_late_init)]
 /*
 Possible comprarison operations
 
-FOR NUMERIC VARIABLES:
+FOR NUMERIC VARIABLES (negative, decimal and big numbers too):
     >           Larger than
     <           Smaller than
     => / >=     Larger or equal
@@ -19,6 +23,10 @@ FOR TEXT VALUES:
     = / ==      Equal
     ~           Contained
     != / =!     Different
+    matches     Matches a regular expression
+    like        Matches a pattern with "*", "?" and "[...]"
+    i=, i==, i~, i!=, i=!, imatches, ilike - The same, but the size of letters doesn't matter
+Numbers are treated like texts when they are compared with "~", "matches", "like" or any of the "i" operators.
 
 When you want to use command's STDOUT of while comparing - use OUT:command
 When you want to use command's STDERR of while comparing - use ERR:command
@@ -38,7 +46,10 @@ fn main() {
     let mut action = String::new();
     let mut right = String::new();
     // List of known operators
-    let operators = [">", "<", "=>", ">=", "=<", "<=", "=", "==", "!=", "=!", "~"];
+    let operators = [
+        ">", "<", "=>", ">=", "=<", "<=", "=", "==", "!=", "=!", "~", "matches", "like", "i=", "i==",
+        "i~", "i!=", "i=!", "imatches", "ilike",
+    ];
 
     let mut i = 1;
     while i < args.len() {
@@ -68,9 +79,11 @@ fn main() {
     left = replace_contents(left.to_string());
     right = replace_contents(right.to_string());
 
-    // Are we comparing numbers?
-    let left_is_numeric = left.trim().parse::<BigInt>().is_ok();
-    let right_is_numeric = right.trim().parse::<BigInt>().is_ok();
+    // Are we comparing numbers? Operators made for texts don't care.
+    let text_only = ["~", "matches", "like", "i=", "i==", "i~", "i!=", "i=!", "imatches", "ilike"]
+        .contains(&action.as_str());
+    let left_is_numeric = !text_only && left.trim().parse::<Number>().is_ok();
+    let right_is_numeric = !text_only && right.trim().parse::<Number>().is_ok();
     //dbg!(&left, left_is_numeric, &right, right_is_numeric);
 
     // The variable "comparison_status" will be used by process::exit to return 0 or 1
@@ -78,8 +91,8 @@ fn main() {
     // Compare data
     match (left_is_numeric, right_is_numeric) {
         (true, true) => {
-            let left_number = left.trim().parse::<BigInt>().unwrap();
-            let right_number = right.trim().parse::<BigInt>().unwrap();
+            let left_number = left.trim().parse::<Number>().unwrap();
+            let right_number = right.trim().parse::<Number>().unwrap();
             comparison_status = match action.as_str() {
                 ">" => left_number > right_number,
                 "<" => left_number < right_number,
@@ -94,10 +107,25 @@ fn main() {
             };
         }
         (false, false) => {
+            let (left, right) = (left.trim(), right.trim());
             comparison_status = match action.as_str() {
-                "=" | "==" => left.trim() == right.trim(),
-                "~" => left.trim().contains(right.trim()),
-                "!=" | "=!" => left.trim() != right.trim(),
+                "=" | "==" => left == right,
+                "~" => left.contains(right),
+                "!=" | "=!" => left != right,
+                "matches" | "imatches" => {
+                    match RegexBuilder::new(right).case_insensitive(action == "imatches").build() {
+                        Ok(regex) => regex.is_match(left),
+                        Err(e) => {
+                            eprintln!("SYNTAX ERROR! Wrong regular expression: {e}");
+                            process::exit(1);
+                        }
+                    }
+                }
+                "like" => glob::matches_text(right, left),
+                "i=" | "i==" => left.to_lowercase() == right.to_lowercase(),
+                "i~" => left.to_lowercase().contains(&right.to_lowercase()),
+                "i!=" | "i=!" => left.to_lowercase() != right.to_lowercase(),
+                "ilike" => glob::matches_text(&right.to_lowercase(), &left.to_lowercase()),
                 _ => {
                     eprintln!("SYNTAX ERROR! Unknown comparison operator!");
                     process::exit(1);
@@ -141,7 +169,17 @@ fn replace_contents(text:String) -> String {
             // println!("{:?}", ewygerfeue);
         }
         
-        let output = getoutput_exec(&ewygerfeue);
+        if ewygerfeue.is_empty() {
+            eprintln!("SYNTAX ERROR! Missing a command after \"{}\"!", text.trim());
+            process::exit(1);
+        }
+        let output = match process::Command::new(&ewygerfeue[0]).args(&ewygerfeue[1..]).output() {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("{}: Command execution failed: {:?}", ewygerfeue[0], e.kind());
+                process::exit(1);
+            }
+        };
         
         if text.starts_with("OUT:") {
             String::from_utf8(output.stdout).unwrap()
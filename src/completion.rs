@@ -0,0 +1,165 @@
+use crate::{BUILTINS, aliases, functions, variables};
+use std::os::unix::fs::PermissionsExt;
+use std::{env, fs};
+
+// Words that start blocks and commands which are understood by the parser
+const KEYWORDS: [&str; 14] = [
+    "if", "elseif", "else", "endif", "lock", "endlock", "free", "continue", "for", "do", "end",
+    "func", "endfunc", "return",
+];
+
+// Prefixes of things that can be compared by IF
+const IF_PREFIXES: [&str; 5] = ["OK:", "FAIL:", "CODE:", "OUT:", "ERR:"];
+// Prefixes of file checks in IF. They are followed by a path instead of a command.
+const IF_FILE_PREFIXES: [&str; 6] = ["EXISTS:", "FILE:", "DIR:", "READABLE:", "EXECUTABLE:", "EMPTY:"];
+
+/*
+Find out what can be written in place of the word under the cursor.
+Returns the position where this word starts and a sorted list of possible replacements.
+
+First word of a command is completed with keywords, builtins, functions, aliases and programs from $PATH.
+Words starting with "$" are completed with names of variables.
+Inside of IF and ELSEIF, words can also start with OK:, FAIL:, CODE:, OUT: or ERR: followed by a command
+or with EXISTS:, FILE:, DIR:, READABLE:, EXECUTABLE: or EMPTY: followed by a path.
+Everything else is a path to a file.
+*/
+pub fn complete(line: &[char], position: usize) -> (usize, Vec<String>) {
+    let mut start = position;
+    while start > 0 && !ends_word(line, start - 1) {
+        start -= 1;
+    }
+    let word: String = line[start..position].iter().collect();
+    let word = word.replace('\\', "");
+
+    // Words before the cursor that belong to the same command
+    let mut command_start = start;
+    while command_start > 0 && ![',', ';', '|', '\n'].contains(&line[command_start - 1]) {
+        command_start -= 1;
+    }
+    let before: String = line[command_start..start].iter().collect();
+    let first_word = before.split_whitespace().next().map(str::to_lowercase);
+    let inside_if = matches!(first_word.as_deref(), Some("if") | Some("elseif"));
+
+    let mut list: Vec<String> = if let Some(name) = word.strip_prefix("${") {
+        matching(variables::names(), name)
+            .map(|x| format!("${{{x}}}"))
+            .collect()
+    } else if let Some(name) = word.strip_prefix('$') {
+        matching(variables::names(), name)
+            .map(|x| format!("${x}"))
+            .collect()
+    } else if let Some(prefix) = IF_PREFIXES
+        .iter()
+        .find(|p| inside_if && word.to_uppercase().starts_with(*p))
+    {
+        let prefix = &word[..prefix.len()];
+        commands(&word[prefix.len()..])
+            .into_iter()
+            .map(|x| format!("{prefix}{x}"))
+            .collect()
+    } else if let Some(prefix) = IF_FILE_PREFIXES
+        .iter()
+        .find(|p| inside_if && word.to_uppercase().starts_with(*p))
+    {
+        let prefix = &word[..prefix.len()];
+        paths(&word[prefix.len()..])
+            .into_iter()
+            .map(|x| format!("{prefix}{x}"))
+            .collect()
+    } else if first_word.is_none() && !word.contains('/') {
+        commands(&word)
+    } else {
+        let mut list = paths(&word);
+        if inside_if {
+            let prefixes = IF_PREFIXES.iter().chain(IF_FILE_PREFIXES.iter());
+            list.extend(matching(prefixes.map(|x| x.to_string()).collect(), &word.to_uppercase()));
+        }
+        list
+    };
+    list.sort();
+    list.dedup();
+    // Spaces in names have to be escaped, so they stay a part of the word
+    let list = list.into_iter().map(|x| x.replace(' ', "\\ ")).collect();
+    (start, list)
+}
+
+// Unescaped spaces and separators are not a part of the word
+fn ends_word(line: &[char], idx: usize) -> bool {
+    let escaped = idx > 0 && line[idx - 1] == '\\';
+    !escaped && (line[idx].is_whitespace() || [',', ';', '|'].contains(&line[idx]))
+}
+
+fn matching(list: Vec<String>, prefix: &str) -> impl Iterator<Item = String> {
+    list.into_iter().filter(move |x| x.starts_with(prefix))
+}
+
+// Everything that can be run as a command
+fn commands(prefix: &str) -> Vec<String> {
+    let mut list: Vec<String> = Vec::new();
+
+    // Keep the size of letters the user started with: IF, If or if
+    let keywords = KEYWORDS.iter().filter_map(|k| {
+        if k.starts_with(&prefix.to_lowercase()) {
+            Some(if !prefix.is_empty() && prefix.chars().all(|x| x.is_uppercase()) {
+                k.to_uppercase()
+            } else {
+                k.to_string()
+            })
+        } else {
+            None
+        }
+    });
+    list.extend(keywords);
+    list.extend(matching(BUILTINS.map(String::from).to_vec(), prefix));
+    list.extend(matching(functions::names(), prefix));
+    list.extend(matching(aliases::names(), prefix));
+
+    let path = variables::get("PATH").unwrap_or_default();
+    for dir in path.split(':').filter(|x| !x.is_empty()) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) {
+                continue;
+            }
+            // Only files that can be executed
+            if let Ok(meta) = fs::metadata(entry.path())
+                && meta.is_file()
+                && meta.permissions().mode() & 0o111 != 0
+            {
+                list.push(name);
+            }
+        }
+    }
+    list
+}
+
+// Files and directories. Directories end with a slash, so it's easy to continue writing a path.
+fn paths(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rsplit_once('/') {
+        Some((dir, prefix)) => (format!("{dir}/"), prefix),
+        None => (String::new(), word),
+    };
+    let read_from = if dir.is_empty() {
+        env::current_dir().unwrap_or_default()
+    } else {
+        dir.clone().into()
+    };
+    let Ok(entries) = fs::read_dir(read_from) else {
+        return Vec::new();
+    };
+
+    let mut list = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Hidden files are shown only when the user asks for them
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+        let is_dir = fs::metadata(entry.path()).is_ok_and(|x| x.is_dir());
+        list.push(format!("{dir}{name}{}", if is_dir { "/" } else { "" }));
+    }
+    list
+}
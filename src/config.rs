@@ -1,18 +1,56 @@
 #![allow(dead_code)]
+use crate::global::{UndefinedVariables, UnmatchedGlobs};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::process;
 
 #[derive(Serialize, Deserialize)]
+// Options missing in the config file are taken from the defaults
+#[serde(default)]
 pub struct RushConfig {
     pub prompt: String,
+    // Shown instead of the prompt when a command needs more lines, like an unclosed LOCK
+    pub continuation_prompt: String,
     pub aliases: HashMap<String, String>,
+    // Report a failure of a pipeline when any command inside of it fails, not just the last one
+    pub pipefail: bool,
+    // What to do with references to variables that are not set: "error", "empty" or "keep"
+    pub undefined_variables: UndefinedVariables,
+    // What to do with patterns like "*.txt" that don't match any file: "error", "empty" or "keep"
+    pub unmatched_globs: UnmatchedGlobs,
+    // How many commands are remembered in the history file. Set to 0 to disable history.
+    pub history_size: usize,
+    // Directories where GT and PUSHD look for relative paths that are not in the current directory
+    pub cdpath: Vec<String>,
 }
 // `Default` settings for `MyConfig`
 impl ::std::default::Default for RushConfig {
     fn default() -> Self {
         Self {
             prompt: "> ".into(),
+            continuation_prompt: "... ".into(),
             aliases: HashMap::new(),
+            pipefail: false,
+            undefined_variables: UndefinedVariables::Error,
+            unmatched_globs: UnmatchedGlobs::Keep,
+            history_size: 1000,
+            cdpath: Vec::new(),
         }
     }
 }
+
+// Read config file or quit if it's broken
+pub fn load() -> RushConfig {
+    match confy::load("rush", "rush") {
+        Err(e) => {
+            eprintln!("Failed to read config file: {}!", e);
+            process::exit(1)
+        }
+        Ok(e) => e,
+    }
+}
+
+// Write settings back to the config file
+pub fn save(cfg: RushConfig) -> Result<(), String> {
+    confy::store("rush", "rush", cfg).map_err(|e| format!("Failed to write config file: {}!", e))
+}
@@ -1,5 +1,5 @@
 use carrot_libs::args;
-use std::env;
+use std::{env, fs};
 use std::process;
 
 fn main() {
@@ -9,5 +9,11 @@ fn main() {
         eprintln!("This program does not support any options nor switches!");
         process::exit(1);
     }
-    println!("{}", env::current_dir().unwrap().display())
+    // Rush keeps the path used to get here in PWD, even if it goes through symbolic links.
+    // It's shown only if it really points to the current directory.
+    let current = env::current_dir().unwrap();
+    match env::var("PWD") {
+        Ok(pwd) if fs::canonicalize(&pwd).is_ok_and(|x| x == current) => println!("{pwd}"),
+        _ => println!("{}", current.display()),
+    }
 }
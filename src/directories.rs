@@ -1,22 +1,178 @@
-use std::env;
+use crate::variables;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::{env, fs};
+
+/*
+Commands that change the working directory.
+
+gt          - Go to the home directory
+gt -        - Go back to the previous directory
+gt DIR      - Go to DIR. Relative paths that don't exist here are also searched in "cdpath" from the config file
+pushd DIR   - Remember the current directory and go to DIR
+pushd       - Swap the current directory with the last remembered one
+popd        - Go back to the last remembered directory
+dirs        - Show the current directory and remembered ones. "dirs -c" forgets all of them.
+
+PWD and OLDPWD are exported, so programs started by the shell know where it is and where it was before.
+PWD keeps the path the way it was written, even if it goes through symbolic links.
+*/
+
+// Directories remembered by pushd. The last one is on the top.
+static STACK: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// Where to look for relative paths that can't be found in the current directory
+static CDPATH: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn stack() -> MutexGuard<'static, Vec<String>> {
+    STACK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// PWD inherited from the environment may point to some other place
+pub fn init() {
+    variables::set_exported("PWD", current());
+}
+
+pub fn set_cdpath(list: Vec<String>) {
+    *CDPATH.lock().unwrap_or_else(|e| e.into_inner()) = list;
+}
+
+// Working directory of the shell. PWD is used as long as it's correct.
+pub fn current() -> String {
+    let physical = env::current_dir().unwrap_or_default();
+    match variables::get("PWD") {
+        Some(pwd) if fs::canonicalize(&pwd).is_ok_and(|x| x == physical) => pwd,
+        _ => physical.to_string_lossy().to_string(),
+    }
+}
 
 // Change working directory
 pub fn gt(buf: &[String]) -> Result<(), String> {
-    // Check if there is just ONE argument
-    if buf.len() == 1 {
-        Err(("Give me a directory path to go!").to_string())
-    }
     // We can't go to more than one directory at the same time
-    else if buf.len() > 2 {
-        Err(("Cannot go to multiple directories simultaneously!").to_string())
-    } else {
-        return match env::set_current_dir(&buf[1]) {
-            Err(e) => Err(format!(
-                "{}: Cannot go into this directory because of an error: {}",
-                buf[1],
-                e.kind()
-            )),
-            Ok(_) => Ok(()),
-        };
+    if buf.len() > 2 {
+        return Err(("Cannot go to multiple directories simultaneously!").to_string());
+    }
+    match buf.get(1).map(String::as_str) {
+        None => match variables::get("HOME") {
+            Some(home) => change(&home),
+            None => Err(("HOME is not set, so there is no home directory to go!").to_string()),
+        },
+        Some("-") => match variables::get("OLDPWD") {
+            Some(old) => {
+                change(&old)?;
+                println!("{}", current());
+                Ok(())
+            }
+            None => Err(("There is no previous directory to go back to!").to_string()),
+        },
+        Some(dir) => {
+            let found = search(dir);
+            change(&found)?;
+            // Show where we are if the directory was found somewhere else
+            if found != dir {
+                println!("{}", current());
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn pushd(buf: &[String]) -> Result<(), String> {
+    if buf.len() > 2 {
+        return Err(("Cannot go to multiple directories simultaneously!").to_string());
+    }
+    let previous = current();
+    match buf.get(1) {
+        Some(dir) => change(&search(dir))?,
+        None => {
+            let Some(top) = stack().pop() else {
+                return Err(("There are no remembered directories!").to_string());
+            };
+            if let Err(e) = change(&top) {
+                stack().push(top);
+                return Err(e);
+            }
+        }
+    }
+    stack().push(previous);
+    show();
+    Ok(())
+}
+
+pub fn popd(buf: &[String]) -> Result<(), String> {
+    if buf.len() > 1 {
+        return Err(("This command does not take any arguments!").to_string());
+    }
+    let Some(top) = stack().pop() else {
+        return Err(("There are no remembered directories!").to_string());
+    };
+    if let Err(e) = change(&top) {
+        stack().push(top);
+        return Err(e);
+    }
+    show();
+    Ok(())
+}
+
+pub fn dirs(buf: &[String]) -> Result<(), String> {
+    match buf.get(1).map(String::as_str) {
+        None => show(),
+        Some("-c") if buf.len() == 2 => stack().clear(),
+        Some(_) => return Err(("Only \"-c\" switch is supported!").to_string()),
+    }
+    Ok(())
+}
+
+// Current directory first, then remembered ones from the newest
+fn show() {
+    let mut list = vec![current()];
+    list.extend(stack().iter().rev().cloned());
+    println!("{}", list.join(" "));
+}
+
+// Look for a relative path in directories from "cdpath". Paths starting with a dot are never searched.
+fn search(dir: &str) -> String {
+    let path = Path::new(dir);
+    if path.is_absolute() || dir.starts_with('.') || path.is_dir() {
+        return dir.to_string();
+    }
+    let cdpath = CDPATH.lock().unwrap_or_else(|e| e.into_inner());
+    for base in cdpath.iter() {
+        let candidate = Path::new(base).join(dir);
+        if candidate.is_dir() {
+            return candidate.to_string_lossy().to_string();
+        }
+    }
+    dir.to_string()
+}
+
+// Go to the directory and update PWD and OLDPWD
+fn change(dir: &str) -> Result<(), String> {
+    let previous = current();
+    let logical = normalize(&Path::new(&previous).join(dir));
+    // ".." after a symbolic link goes back to where the link is
+    env::set_current_dir(&logical).map_err(|e| {
+        format!(
+            "{}: Cannot go into this directory because of an error: {}",
+            dir,
+            e.kind()
+        )
+    })?;
+    variables::set_exported("OLDPWD", previous);
+    variables::set_exported("PWD", logical.to_string_lossy().to_string());
+    Ok(())
+}
+
+// Remove "." and ".." from the path without looking at the disk
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
     }
+    normal
 }
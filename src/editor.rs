@@ -0,0 +1,347 @@
+use crate::{completion, history};
+use console::{Key, Term, measure_text_width};
+use std::io;
+
+/*
+Simple line editor for the interactive mode.
+
+Supported keys:
+LEFT, RIGHT, HOME, END, CTRL+A, CTRL+E - Move the cursor
+BACKSPACE, DELETE - Remove a letter
+CTRL+U, CTRL+K - Remove everything before/after the cursor
+CTRL+W - Remove a word before the cursor
+UP, DOWN - Browse history
+CTRL+R - Search in history. Press CTRL+R again to find older commands.
+TAB - Complete a command, path or variable name. Show all possibilities if there is more than one.
+CTRL+C - Forget the line
+CTRL+D - Quit the shell when the line is empty
+*/
+
+const CTRL_A: char = '\x01';
+const CTRL_D: char = '\x04';
+const CTRL_E: char = '\x05';
+const CTRL_G: char = '\x07';
+const CTRL_K: char = '\x0b';
+const CTRL_R: char = '\x12';
+const CTRL_U: char = '\x15';
+const CTRL_W: char = '\x17';
+
+pub enum Input {
+    Line(String),
+    // CTRL+C
+    Cancelled,
+    // CTRL+D or end of the input
+    Quit,
+}
+
+struct Editor {
+    term: Term,
+    // Last line of the prompt. Earlier lines are printed only once.
+    prompt: String,
+    line: Vec<char>,
+    // Position of the cursor in the line
+    position: usize,
+    // Row of the terminal where the cursor is, counted from the first row of the prompt
+    cursor_row: usize,
+    history: Vec<String>,
+    // Which history entry is shown. It's equal to the length of history when user edits a new line.
+    history_idx: usize,
+    // Line that was being edited before the user started browsing history
+    draft: Vec<char>,
+}
+
+pub fn read_line(prompt: &str) -> io::Result<Input> {
+    let term = Term::stderr();
+
+    // There is nothing to edit when commands are not typed by a human
+    if !term.is_term() {
+        eprint!("{prompt}");
+        let mut line = String::new();
+        return match io::stdin().read_line(&mut line)? {
+            0 => Ok(Input::Quit),
+            _ => Ok(Input::Line(line.trim_end_matches(['\n', '\r']).to_string())),
+        };
+    }
+
+    let (earlier_lines, last_line) = match prompt.rsplit_once('\n') {
+        Some((earlier, last)) => (Some(earlier), last),
+        None => (None, prompt),
+    };
+    if let Some(earlier) = earlier_lines {
+        term.write_line(earlier)?;
+    }
+
+    let history = history::entries();
+    let mut editor = Editor {
+        term,
+        prompt: last_line.to_string(),
+        line: Vec::new(),
+        position: 0,
+        cursor_row: 0,
+        history_idx: history.len(),
+        history,
+        draft: Vec::new(),
+    };
+    editor.run()
+}
+
+impl Editor {
+    fn run(&mut self) -> io::Result<Input> {
+        self.render(&self.prompt.clone())?;
+        loop {
+            match self.term.read_key_raw()? {
+                Key::Enter => return self.finish().map(Input::Line),
+                Key::CtrlC => {
+                    self.position = self.line.len();
+                    self.render(&self.prompt.clone())?;
+                    self.term.write_line("^C")?;
+                    return Ok(Input::Cancelled);
+                }
+                Key::Char(CTRL_D) if self.line.is_empty() => {
+                    self.term.write_line("")?;
+                    return Ok(Input::Quit);
+                }
+                Key::Char(CTRL_R) => {
+                    if let Some(accepted) = self.search()? {
+                        return Ok(Input::Line(accepted));
+                    }
+                }
+                Key::Tab => self.complete()?,
+                key => self.edit(key),
+            }
+            self.render(&self.prompt.clone())?;
+        }
+    }
+
+    // Change the line after a key press
+    fn edit(&mut self, key: Key) {
+        match key {
+            Key::Char(CTRL_A) | Key::Home => self.position = 0,
+            Key::Char(CTRL_E) | Key::End => self.position = self.line.len(),
+            Key::ArrowLeft => self.position = self.position.saturating_sub(1),
+            Key::ArrowRight => self.position = (self.position + 1).min(self.line.len()),
+            Key::Backspace if self.position > 0 => {
+                self.position -= 1;
+                self.line.remove(self.position);
+            }
+            Key::Del | Key::Char(CTRL_D) if self.position < self.line.len() => {
+                self.line.remove(self.position);
+            }
+            Key::Char(CTRL_U) => {
+                self.line.drain(..self.position);
+                self.position = 0;
+            }
+            Key::Char(CTRL_K) => self.line.truncate(self.position),
+            Key::Char(CTRL_W) => {
+                let mut start = self.position;
+                while start > 0 && self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.line.drain(start..self.position);
+                self.position = start;
+            }
+            Key::ArrowUp if self.history_idx > 0 => {
+                if self.history_idx == self.history.len() {
+                    self.draft = self.line.clone();
+                }
+                self.history_idx -= 1;
+                self.set_line(self.history[self.history_idx].chars().collect());
+            }
+            Key::ArrowDown if self.history_idx < self.history.len() => {
+                self.history_idx += 1;
+                let line = match self.history.get(self.history_idx) {
+                    Some(entry) => entry.chars().collect(),
+                    None => self.draft.clone(),
+                };
+                self.set_line(line);
+            }
+            Key::Char(c) if !c.is_control() => self.insert(c),
+            _ => (),
+        }
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        let (start, list) = completion::complete(&self.line, self.position);
+        let Some(first) = list.first() else {
+            return Ok(());
+        };
+
+        // Write as much as possible. Add a space after the word if there is nothing more to write.
+        let replacement: String = if list.len() == 1 {
+            if first.ends_with('/') {
+                first.clone()
+            } else {
+                format!("{first} ")
+            }
+        } else {
+            first
+                .chars()
+                .enumerate()
+                .take_while(|(idx, c)| list.iter().all(|x| x.chars().nth(*idx) == Some(*c)))
+                .map(|(_, c)| c)
+                .collect()
+        };
+
+        if replacement.chars().count() > self.position - start {
+            self.line.splice(start..self.position, replacement.chars());
+            self.position = start + replacement.chars().count();
+        } else if list.len() > 1 {
+            self.show_list(&list)?;
+        }
+        Ok(())
+    }
+
+    // Print possible completions in columns below the line. The line is drawn again after them.
+    fn show_list(&mut self, list: &[String]) -> io::Result<()> {
+        let position = self.position;
+        self.position = self.line.len();
+        self.render(&self.prompt.clone())?;
+        self.position = position;
+        self.term.write_line("")?;
+
+        let width = (self.term.size().1 as usize).max(1);
+        let column = list.iter().map(|x| measure_text_width(x)).max().unwrap_or(0) + 2;
+        let columns = (width / column).max(1);
+        for row in list.chunks(columns) {
+            let row: String = row.iter().map(|x| format!("{x:<column$}")).collect();
+            self.term.write_line(row.trim_end())?;
+        }
+        self.cursor_row = 0;
+        Ok(())
+    }
+
+    fn insert(&mut self, c: char) {
+        self.line.insert(self.position, c);
+        self.position += 1;
+    }
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.position = line.len();
+        self.line = line;
+    }
+
+    /*
+    Reverse incremental search. Every typed letter looks for the newest command containing the query.
+    ENTER runs the command that was found, CTRL+R looks for an older one,
+    arrows and ESC put the found command in the line so it can be edited, CTRL+C and CTRL+G cancel searching.
+    */
+    fn search(&mut self) -> io::Result<Option<String>> {
+        let mut query = String::new();
+        // Index of the history entry that matches the query
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+
+        loop {
+            let label = format!(
+                "({}reverse-i-search)`{query}': ",
+                if failed { "failed " } else { "" }
+            );
+            let matched: Vec<char> = match found {
+                Some(idx) => self.history[idx].chars().collect(),
+                None => Vec::new(),
+            };
+            let position = match found {
+                Some(idx) => self.history[idx].find(&query).map_or(0, |x| {
+                    self.history[idx][..x].chars().count()
+                }),
+                None => 0,
+            };
+            self.line = matched;
+            self.position = position;
+            self.render(&label)?;
+
+            let key = self.term.read_key_raw()?;
+            // Older entries are searched when CTRL+R is pressed again
+            let search_from = match key {
+                Key::Char(CTRL_R) => found.unwrap_or(self.history.len()),
+                Key::Backspace => {
+                    query.pop();
+                    self.history.len()
+                }
+                Key::Char(c) if !c.is_control() => {
+                    query.push(c);
+                    found.map_or(self.history.len(), |x| x + 1)
+                }
+                Key::Enter => {
+                    self.history_idx = self.history.len();
+                    return self.finish().map(Some);
+                }
+                Key::CtrlC | Key::Char(CTRL_G) => {
+                    self.set_line(Vec::new());
+                    return Ok(None);
+                }
+                Key::Escape | Key::ArrowLeft | Key::ArrowRight | Key::Home | Key::End => {
+                    self.position = self.line.len();
+                    return Ok(None);
+                }
+                _ => continue,
+            };
+
+            let result = self.history[..search_from]
+                .iter()
+                .rposition(|entry| entry.contains(&query));
+            failed = result.is_none() && !query.is_empty();
+            if result.is_some() || query.is_empty() {
+                found = result;
+            }
+        }
+    }
+
+    // Move the cursor after the line, so the output of the command does not cover it
+    fn finish(&mut self) -> io::Result<String> {
+        self.position = self.line.len();
+        self.render(&self.prompt.clone())?;
+        self.term.write_line("")?;
+        Ok(self.line.iter().collect())
+    }
+
+    // Draw the prompt with the line again and put the cursor in the right place
+    fn render(&mut self, prompt: &str) -> io::Result<()> {
+        let width = (self.term.size().1 as usize).max(1);
+        let line: String = self.line.iter().collect();
+        let before_cursor: String = self.line[..self.position].iter().collect();
+
+        self.term.move_cursor_up(self.cursor_row)?;
+        self.term.write_str("\r\x1b[J")?;
+        self.term.write_str(prompt)?;
+        self.term.write_str(&line.replace('\n', "\r\n"))?;
+
+        let prompt_width = measure_text_width(prompt);
+        let (end_row, end) = last_line_end(&line, prompt_width, width);
+        let (cursor_row, cursor) = last_line_end(&before_cursor, prompt_width, width);
+
+        // Terminal does not move to the next row until something is written after the last column
+        if end > 0 && end.is_multiple_of(width) {
+            self.term.write_str("\r\n")?;
+        }
+
+        self.term.move_cursor_up(end_row + end / width - cursor_row - cursor / width)?;
+        self.term.write_str("\r")?;
+        self.term.move_cursor_right(cursor % width)?;
+        self.cursor_row = cursor_row + cursor / width;
+        self.term.flush()
+    }
+}
+
+/*
+Commands taken from history can have many lines: for x in a b do <ENTER> say $x <ENTER> end
+Find the row where the last of them starts and its width, counted from the given column of the first row.
+*/
+fn last_line_end(text: &str, column: usize, width: usize) -> (usize, usize) {
+    let mut lines = text.split('\n');
+    let mut row = 0;
+    let mut end = column + measure_text_width(lines.next().unwrap_or(""));
+    for line in lines {
+        // Line that fills the last column moves to the next row only once
+        row += if end > 0 && end.is_multiple_of(width) {
+            end / width
+        } else {
+            end / width + 1
+        };
+        end = measure_text_width(line);
+    }
+    (row, end)
+}
@@ -1,15 +1,262 @@
+use crate::jobs::{self, Job};
+use crate::variables;
+use crate::parser::RedirectionKind;
 use io::Write;
-use std::{io, process};
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::thread::JoinHandle;
+use std::{io, process, thread};
+
+// Command with it's arguments and redirections ready to be executed
+pub struct Stage {
+    pub args: Vec<String>,
+    // Kind of redirection and a file name (there is no file name for 2>&1)
+    pub redirections: Vec<(RedirectionKind, Option<String>)>,
+}
+
+// Program with it's arguments. Programs can only see variables that were exported.
+fn command(args: &[String]) -> process::Command {
+    let mut command = process::Command::new(&args[0]);
+    command.args(&args[1..]).env_clear().envs(variables::exported());
+    command
+}
 
 pub fn exec(args: &[String]) -> Result<Option<i32>, String> {
-    // Run a command passed in "args[0]" with arguments in "args[1..]" and get it's output with
-    // using process::Command::new().args().stdout(stdout()).output();
-    match process::Command::new(&args[0]).args(&args[1..]).status() {
-        Err(e) => Err(format!("Command execution failed: {:?}", e.kind())),
-        Ok(process) => {
-            io::stdout().flush().unwrap();
-            Ok(process.code())
+    // Single command is just a pipeline with only one element
+    pipeline(
+        vec![Stage {
+            args: args.to_vec(),
+            redirections: Vec::new(),
+        }],
+        false,
+    )
+}
+
+/*
+Run commands connected with pipes like: say hello | grep hell | wc -c
+STDOUT of every command is connected to STDIN of the next one.
+First command reads from the terminal and the last one writes to it.
+Redirections can change that, so "say hello > file.txt | wc -c" will write to the file and "wc" will read nothing.
+
+Exit code of the last command is returned.
+If "pipefail" is enabled, exit code of the last command that failed is returned instead.
+Pipelines started in the background return 0 right away.
+*/
+pub fn pipeline(stages: Vec<Stage>, background: bool) -> Result<Option<i32>, String> {
+    // Prepare where every command reads from and writes to before anything is started.
+    // If some file can't be opened, nothing will run.
+    // None means that the command uses the terminal.
+    let mut streams: Vec<(Option<OwnedFd>, Option<OwnedFd>, Option<OwnedFd>)> = Vec::new();
+    let mut previous_reader = None;
+
+    for (idx, stage) in stages.iter().enumerate() {
+        let mut stdin: Option<OwnedFd> = previous_reader.take();
+        let mut stdout: Option<OwnedFd> = None;
+        let mut stderr: Option<OwnedFd> = None;
+
+        if idx < stages.len() - 1 {
+            let (reader, writer) =
+                io::pipe().map_err(|e| format!("Failed to create a pipe: {:?}", e.kind()))?;
+            previous_reader = Some(reader.into());
+            stdout = Some(writer.into());
+        }
+
+        for (kind, target) in &stage.redirections {
+            let target = target.as_deref().unwrap_or_default();
+            match kind {
+                RedirectionKind::Input => stdin = Some(open_file(target, *kind)?),
+                RedirectionKind::Output | RedirectionKind::Append => {
+                    stdout = Some(open_file(target, *kind)?)
+                }
+                RedirectionKind::Error | RedirectionKind::ErrorAppend => {
+                    stderr = Some(open_file(target, *kind)?)
+                }
+                // STDERR goes wherever STDOUT goes at this moment
+                RedirectionKind::ErrorToOutput => {
+                    stderr = match &stdout {
+                        Some(fd) => Some(fd.try_clone().map_err(|e| {
+                            format!("Failed to redirect STDERR to STDOUT: {:?}", e.kind())
+                        })?),
+                        None => None,
+                    }
+                }
+            }
+        }
+        streams.push((stdin, stdout, stderr));
+    }
+
+    // Every command of the pipeline is in the same process group. The group is named after the first command.
+    let mut pgid: Option<i32> = None;
+    let mut pids: Vec<i32> = Vec::new();
+    for (stage, (stdin, stdout, stderr)) in stages.iter().zip(streams) {
+        let mut command = command(&stage.args);
+        if let Some(fd) = stdin {
+            command.stdin(process::Stdio::from(fd));
+        }
+        if let Some(fd) = stdout {
+            command.stdout(process::Stdio::from(fd));
+        }
+        if let Some(fd) = stderr {
+            command.stderr(process::Stdio::from(fd));
+        }
+        if jobs::job_control() {
+            command.process_group(pgid.unwrap_or(0));
+            unsafe {
+                command.pre_exec(move || {
+                    jobs::prepare_child(!background);
+                    Ok(())
+                });
+            }
+        }
+
+        match command.spawn() {
+            Err(e) => {
+                // Don't leave the rest of the pipeline hanging
+                for pid in &pids {
+                    unsafe {
+                        libc::kill(*pid, libc::SIGKILL);
+                        libc::waitpid(*pid, std::ptr::null_mut(), 0);
+                    }
+                }
+                return Err(format!(
+                    "{}: Command execution failed: {:?}",
+                    stage.args[0],
+                    e.kind()
+                ));
+            }
+            Ok(child) => {
+                let pid = child.id() as i32;
+                if jobs::job_control() && pgid.is_none() {
+                    pgid = Some(pid);
+                }
+                pids.push(pid);
+            }
         }
     }
+
+    let command = stages
+        .iter()
+        .map(|x| x.args.join(" "))
+        .collect::<Vec<String>>()
+        .join(" | ");
+    let job = Job::new(pgid, pids, command);
+    if background {
+        jobs::background(job);
+        return Ok(Some(0));
+    }
+
+    let code = jobs::foreground(job);
     // Flush stdout
+    io::stdout().flush().unwrap();
+    Ok(Some(code))
+}
+
+// Run a command and collect everything it printed to STDOUT. STDERR goes to the terminal.
+pub fn output(args: &[String]) -> Result<String, String> {
+    let (stdout, _) = collect(args, false)?;
+    String::from_utf8(stdout).map_err(|_| "Got non UTF-8 data from stdout".to_string())
+}
+
+/*
+Run a command and collect everything it printed to STDOUT and optionally to STDERR.
+It stays in the process group of the shell, but signals are set up just like for other jobs.
+If it gets stopped anyway (kill -STOP), it's put on the list of jobs and there is nothing to collect.
+*/
+pub fn collect(args: &[String], with_stderr: bool) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut command = command(args);
+    command.stdout(process::Stdio::piped());
+    if with_stderr {
+        command.stderr(process::Stdio::piped());
+    }
+    if jobs::job_control() {
+        unsafe {
+            command.pre_exec(|| {
+                jobs::prepare_child(false);
+                Ok(())
+            });
+        }
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("{}: Command execution failed: {:?}", args[0], e.kind()))?;
+
+    // Pipes are read in the meantime, so commands that print a lot won't get stuck
+    let stdout = child.stdout.take().map(read_all);
+    let stderr = child.stderr.take().map(read_all);
+    let job = Job::new(None, vec![child.id() as i32], args.join(" "));
+    if jobs::wait_foreground(job).is_none() {
+        return Err(format!("{}: Command was stopped before it finished", args[0]));
+    }
+    let finish = |reader: Option<JoinHandle<io::Result<Vec<u8>>>>| match reader {
+        Some(reader) => reader
+            .join()
+            .map_err(|_| "Failed to collect the output".to_string())?
+            .map_err(|e| format!("Failed to read the output: {:?}", e.kind())),
+        None => Ok(Vec::new()),
+    };
+    Ok((finish(stdout)?, finish(stderr)?))
+}
+
+// Read everything from a pipe in a separate thread
+fn read_all(mut reader: impl Read + Send + 'static) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).map(|_| buf)
+    })
+}
+
+/*
+Run something inside of the shell with STDOUT going to a pipe and collect everything that was written there.
+Builtins, functions and programs started in the meantime write to the pipe too.
+*/
+pub fn capture<F: FnOnce()>(f: F) -> Result<String, String> {
+    io::stdout().flush().unwrap();
+    let (reader, writer) =
+        io::pipe().map_err(|e| format!("Failed to create a pipe: {:?}", e.kind()))?;
+
+    // Read the pipe in the meantime, so commands that print a lot won't get stuck
+    let collector = read_all(reader);
+
+    let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if saved < 0 {
+        return Err("Failed to save STDOUT".to_string());
+    }
+    unsafe {
+        libc::dup2(writer.as_raw_fd(), libc::STDOUT_FILENO);
+    }
+    drop(writer);
+
+    f();
+
+    // Put the original STDOUT back. This closes the last end of the pipe that we have, so the collector can finish.
+    io::stdout().flush().unwrap();
+    unsafe {
+        libc::dup2(saved, libc::STDOUT_FILENO);
+        libc::close(saved);
+    }
+
+    let buf = collector
+        .join()
+        .map_err(|_| "Failed to collect the output".to_string())?
+        .map_err(|e| format!("Failed to read the output: {:?}", e.kind()))?;
+    String::from_utf8(buf).map_err(|_| "Got non UTF-8 data from stdout".to_string())
+}
+
+fn open_file(name: &str, kind: RedirectionKind) -> Result<OwnedFd, String> {
+    let file = match kind {
+        RedirectionKind::Input => File::open(name),
+        RedirectionKind::Append | RedirectionKind::ErrorAppend => {
+            OpenOptions::new().append(true).create(true).open(name)
+        }
+        _ => File::create(name),
+    };
+    match file {
+        Ok(f) => Ok(f.into()),
+        Err(e) => Err(format!(
+            "{name}: Can't open a file for redirection: {:?}",
+            e.kind()
+        )),
+    }
 }
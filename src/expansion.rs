@@ -0,0 +1,279 @@
+use crate::global::take_substitution;
+use crate::variables;
+use std::ffi;
+
+/*
+Expansions that work on the text of a word before variables are resolved.
+
+Braces give a separate word for every element:
+say file.{rs,toml}      -- file.rs file.toml
+say {1..5}              -- 1 2 3 4 5
+say {a..e..2}           -- a c e
+say {01..10}            -- 01 02 ... 10
+Braces in quotation marks, escaped ones and those without a comma or ".." are left as they are.
+Ranges can't give more than a million elements.
+
+Tilde at the beginning of a word is a home directory:
+gt ~/Documents          -- /home/user/Documents
+say ~root               -- /root
+*/
+
+pub fn braces(word: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = word.chars().collect();
+    let Some((open, close, items)) = find_braces(&chars)? else {
+        return Ok(vec![word.to_string()]);
+    };
+    let prefix: String = chars[..open].iter().collect();
+    let suffix: String = chars[close + 1..].iter().collect();
+
+    // Elements and the rest of the word may have more braces inside
+    let mut words = Vec::new();
+    for item in items {
+        words.extend(braces(&format!("{prefix}{item}{suffix}"))?);
+    }
+    Ok(words)
+}
+
+pub fn tilde(word: &str) -> String {
+    let Some(rest) = word.strip_prefix('~') else {
+        return word.to_string();
+    };
+    let (user, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if !user
+        .chars()
+        .all(|x| x.is_alphanumeric() || ['_', '-', '.'].contains(&x))
+    {
+        return word.to_string();
+    }
+    let home = if user.is_empty() {
+        variables::get("HOME")
+    } else {
+        home_of(user)
+    };
+    match home {
+        // Every letter of the directory is escaped, so it's not treated like a variable or a wildcard
+        Some(home) => {
+            let home: String = home.chars().flat_map(|x| ['\\', x]).collect();
+            format!("{home}{path}")
+        }
+        None => word.to_string(),
+    }
+}
+
+fn home_of(user: &str) -> Option<String> {
+    let name = ffi::CString::new(user).ok()?;
+    unsafe {
+        let passwd = libc::getpwnam(name.as_ptr());
+        if passwd.is_null() {
+            return None;
+        }
+        Some(
+            ffi::CStr::from_ptr((*passwd).pw_dir)
+                .to_string_lossy()
+                .to_string(),
+        )
+    }
+}
+
+// Find the first unquoted "{...}" that can be expanded.
+// Returns where it starts, where it ends and elements inside of it.
+fn find_braces(chars: &[char]) -> Result<Option<(usize, usize, Vec<String>)>, String> {
+    let mut quote: Option<char> = None;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c == '\\' {
+            idx += 1;
+        } else if c == '$' && quote != Some('\'') {
+            idx = skip_dollar(chars, idx);
+        } else if quote.is_none() && (c == '\'' || c == '"') {
+            quote = Some(c);
+        } else if Some(c) == quote {
+            quote = None;
+        } else if c == '{'
+            && quote.is_none()
+            && let Some((close, items)) = group(chars, idx)?
+        {
+            return Ok(Some((idx, close, items)));
+        }
+        idx += 1;
+    }
+    Ok(None)
+}
+
+// "${NAME}" and "$(command)" are not braces. Returns the position of the last letter that belongs to them.
+fn skip_dollar(chars: &[char], idx: usize) -> usize {
+    match chars.get(idx + 1) {
+        Some('{') => chars[idx..]
+            .iter()
+            .position(|x| *x == '}')
+            .map_or(chars.len(), |x| idx + x),
+        Some('(') => {
+            let mut rest = chars[idx + 2..].iter().copied();
+            match take_substitution(&mut rest) {
+                Ok(script) => idx + 2 + script.chars().count(),
+                Err(_) => chars.len(),
+            }
+        }
+        _ => idx,
+    }
+}
+
+// Split "{a,b,c}" into elements or generate them from "{a..e}"
+fn group(chars: &[char], open: usize) -> Result<Option<(usize, Vec<String>)>, String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut idx = open + 1;
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c == '\\' {
+            item.extend(chars.get(idx..idx + 2).unwrap_or(&chars[idx..]));
+            idx += 2;
+            continue;
+        }
+        if c == '$' && quote != Some('\'') {
+            let end = skip_dollar(chars, idx);
+            item.extend(&chars[idx..(end + 1).min(chars.len())]);
+            idx = end + 1;
+            continue;
+        }
+        if quote.is_none() && (c == '\'' || c == '"') {
+            quote = Some(c);
+        } else if Some(c) == quote {
+            quote = None;
+        } else if quote.is_none() && c == '{' {
+            depth += 1;
+        } else if quote.is_none() && c == '}' && depth > 0 {
+            depth -= 1;
+        } else if quote.is_none() && c == '}' {
+            items.push(item);
+            return if items.len() > 1 {
+                Ok(Some((idx, items)))
+            } else {
+                Ok(range(&items[0])?.map(|x| (idx, x)))
+            };
+        } else if quote.is_none() && c == ',' && depth == 0 {
+            items.push(item);
+            item = String::new();
+            idx += 1;
+            continue;
+        }
+        item.push(c);
+        idx += 1;
+    }
+    Ok(None)
+}
+
+// Numbers or letters from "1..10", "10..1", "a..z" with an optional step: "1..10..2"
+fn range(text: &str) -> Result<Option<Vec<String>>, String> {
+    let parts: Vec<&str> = text.split("..").collect();
+    if parts.len() != 2 && parts.len() != 3 {
+        return Ok(None);
+    }
+    let step: i64 = match parts.get(2).map(|x| x.parse::<i64>().ok().and_then(i64::checked_abs)) {
+        Some(Some(x)) => x.max(1),
+        Some(None) => return Ok(None),
+        None => 1,
+    };
+
+    if let (Ok(start), Ok(end)) = (parts[0].parse::<i64>(), parts[1].parse::<i64>()) {
+        // "01..10" keeps the same width for every number
+        let padded = [parts[0], parts[1]]
+            .iter()
+            .any(|x| x.trim_start_matches('-').starts_with('0') && x.trim_start_matches('-').len() > 1);
+        let width = if padded {
+            parts[0].len().max(parts[1].len())
+        } else {
+            0
+        };
+        return Ok(Some(
+            steps(text, start, end, step)?
+                .map(|x| format!("{x:0width$}"))
+                .collect(),
+        ));
+    }
+
+    let mut start = parts[0].chars();
+    let mut end = parts[1].chars();
+    match (start.next(), start.next(), end.next(), end.next()) {
+        (Some(start), None, Some(end), None)
+            if start.is_ascii_alphabetic() && end.is_ascii_alphabetic() =>
+        {
+            Ok(Some(
+                steps(text, start as i64, end as i64, step)?
+                    .map(|x| (x as u8 as char).to_string())
+                    .collect(),
+            ))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Most elements a range can give. Bigger ones would just eat all of the memory.
+const MAX_RANGE: i128 = 1_000_000;
+
+// Numbers are counted with i128, so ranges close to the limits of i64 don't overflow
+fn steps(text: &str, start: i64, end: i64, step: i64) -> Result<impl Iterator<Item = i64>, String> {
+    let (start, end, step) = (start as i128, end as i128, step as i128);
+    let count = (start - end).abs() / step + 1;
+    if count > MAX_RANGE {
+        return Err(format!("Range \"{{{text}}}\" gives more than {MAX_RANGE} elements"));
+    }
+    // Every element is between start and end, so it always fits in i64
+    Ok((0..count).map(move |x| {
+        if start <= end {
+            (start + x * step) as i64
+        } else {
+            (start - x * step) as i64
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(word: &str) -> String {
+        braces(word).unwrap().join(" ")
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(expanded("file.{rs,toml}"), "file.rs file.toml");
+        assert_eq!(expanded("a{b,c{d,e}}f"), "abf acdf acef");
+        assert_eq!(expanded("'{x,y}'"), "'{x,y}'");
+        assert_eq!(expanded("\\{x,y}"), "\\{x,y}");
+        assert_eq!(expanded("{single}"), "{single}");
+        assert_eq!(expanded("${x}{1,2}"), "${x}1 ${x}2");
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(expanded("{1..5}"), "1 2 3 4 5");
+        assert_eq!(expanded("{5..1..2}"), "5 3 1");
+        assert_eq!(expanded("{-2..2}"), "-2 -1 0 1 2");
+        assert_eq!(expanded("{01..10..3}"), "01 04 07 10");
+        assert_eq!(expanded("{a..e..2}"), "a c e");
+        assert_eq!(expanded("{a..}"), "{a..}");
+    }
+
+    #[test]
+    fn ranges_at_the_limits() {
+        assert_eq!(
+            expanded("{9223372036854775806..9223372036854775807}"),
+            "9223372036854775806 9223372036854775807"
+        );
+        assert_eq!(
+            expanded("{-9223372036854775807..-9223372036854775808}"),
+            "-9223372036854775807 -9223372036854775808"
+        );
+        assert_eq!(
+            expanded("{0..9223372036854775807..9223372036854775807}"),
+            "0 9223372036854775807"
+        );
+        assert!(braces("{-9223372036854775808..9223372036854775807}").is_err());
+        assert!(braces("{1..2000000}").is_err());
+    }
+}
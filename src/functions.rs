@@ -0,0 +1,43 @@
+use crate::parser::Node;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Functions defined with FUNC ... ENDFUNC
+static FUNCTIONS: Mutex<BTreeMap<String, Arc<Vec<Node>>>> = Mutex::new(BTreeMap::new());
+
+// How many functions are running right now, one inside another
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+// Stop endless recursion before it crashes the shell
+const MAX_DEPTH: usize = 256;
+
+pub fn define(name: &str, body: Vec<Node>) {
+    let mut functions = FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    functions.insert(name.to_string(), Arc::new(body));
+}
+
+pub fn get(name: &str) -> Option<Arc<Vec<Node>>> {
+    let functions = FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    functions.get(name).cloned()
+}
+
+pub fn names() -> Vec<String> {
+    let functions = FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    functions.keys().cloned().collect()
+}
+
+pub fn enter() -> Result<(), String> {
+    if DEPTH.fetch_add(1, Ordering::SeqCst) >= MAX_DEPTH {
+        DEPTH.fetch_sub(1, Ordering::SeqCst);
+        return Err(format!("Too many nested function calls (more than {MAX_DEPTH})"));
+    }
+    Ok(())
+}
+
+pub fn leave() {
+    DEPTH.fetch_sub(1, Ordering::SeqCst);
+}
+
+pub fn depth() -> usize {
+    DEPTH.load(Ordering::SeqCst)
+}
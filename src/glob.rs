@@ -0,0 +1,283 @@
+use std::fs;
+
+/*
+Filename expansion. Patterns come from global::glob_pattern, so every letter
+that should be taken literally has a slash before it.
+
+*       - Any text, even empty
+?       - Exactly one letter
+[abc]   - One of the letters. Ranges like [a-z] work too. [!abc] or [^abc] match everything else
+**      - Whole path element. Any number of directories: src/**/*.rs
+
+Files starting with a dot are matched only if the pattern starts with a dot too.
+Found paths are sorted, so the result is always the same.
+*/
+
+// Are there any wildcards in the pattern?
+pub fn is_pattern(pattern: &str) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut idx = 0;
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' => idx += 1,
+            '*' | '?' => return true,
+            '[' if class_end(&chars[idx..]).is_some() => return true,
+            _ => (),
+        }
+        idx += 1;
+    }
+    false
+}
+
+// Remove slashes from the pattern to get the text that was written
+pub fn unescape(pattern: &str) -> String {
+    let mut output = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => output.extend(chars.next()),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+// Find paths of all files matching the pattern
+pub fn expand(pattern: &str) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+    // "*/" matches only directories
+    let only_dirs = rest.ends_with('/');
+    let parts: Vec<&str> = rest.split('/').filter(|x| !x.is_empty()).collect();
+
+    for (idx, part) in parts.iter().enumerate() {
+        let last = idx == parts.len() - 1;
+        let mut found = Vec::new();
+        for path in &paths {
+            if *part == "**" {
+                // No directories at all
+                if !last {
+                    found.push(path.clone());
+                }
+                descendants(path, !last, &mut found);
+            } else if is_pattern(part) {
+                let part: Vec<char> = part.chars().collect();
+                for name in entries(path, part.first() == Some(&'.')) {
+                    let name_chars: Vec<char> = name.chars().collect();
+                    let full = join(path, &name);
+                    if matches(&part, &name_chars) && ((last && !only_dirs) || is_dir(&full)) {
+                        found.push(full);
+                    }
+                }
+            } else {
+                found.push(join(path, &unescape(part)));
+            }
+        }
+        paths = found;
+    }
+
+    // Parts without wildcards were not checked yet
+    paths.retain(|x| !x.is_empty() && fs::symlink_metadata(x).is_ok());
+    if only_dirs {
+        paths.retain(|x| is_dir(x));
+        for path in paths.iter_mut() {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else if path.ends_with('/') {
+        format!("{path}{name}")
+    } else {
+        format!("{path}/{name}")
+    }
+}
+
+fn is_dir(path: &str) -> bool {
+    fs::metadata(path).is_ok_and(|x| x.is_dir())
+}
+
+// Names of files inside of a directory. Empty path means the current directory.
+fn entries(path: &str, hidden: bool) -> Vec<String> {
+    let Ok(list) = fs::read_dir(if path.is_empty() { "." } else { path }) else {
+        return Vec::new();
+    };
+    list.flatten()
+        .map(|x| x.file_name().to_string_lossy().to_string())
+        .filter(|x| hidden || !x.starts_with('.'))
+        .collect()
+}
+
+// Everything inside of a directory and it's subdirectories, except for hidden files.
+// Links to directories are not followed, so there are no loops.
+fn descendants(path: &str, only_dirs: bool, found: &mut Vec<String>) {
+    let Ok(list) = fs::read_dir(if path.is_empty() { "." } else { path }) else {
+        return;
+    };
+    for entry in list.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let full = join(path, &name);
+        let dir = entry.file_type().is_ok_and(|x| x.is_dir());
+        if dir || !only_dirs {
+            found.push(full.clone());
+        }
+        if dir {
+            descendants(&full, only_dirs, found);
+        }
+    }
+}
+
+// Check a text against a pattern, like in IF: $NAME LIKE "*.rs". "/" is matched like any other letter.
+pub fn matches_text(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+/*
+Letters are matched one by one. When something doesn't match, go back to the last "*"
+and let it take one more letter. Earlier stars never have to take more, so this is always fast.
+*/
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position in the pattern after the last "*" and the letter where it ends right now
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            p += 1;
+            star = Some((p, n));
+        } else if let Some(len) = element(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+        } else if let Some((after, end)) = star {
+            p = after;
+            n = end + 1;
+            star = Some((after, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+// Check a letter against the first element of the pattern. Returns how long that element is.
+fn element(pattern: &[char], c: char) -> Option<usize> {
+    match pattern.first()? {
+        '*' => None,
+        '?' => Some(1),
+        '[' => match class_end(pattern) {
+            Some(end) => in_class(&pattern[1..end], c).then_some(end + 1),
+            // Bracket without a pair is just a bracket
+            None => (c == '[').then_some(1),
+        },
+        '\\' if pattern.len() > 1 => (c == pattern[1]).then_some(2),
+        x => (*x == c).then_some(1),
+    }
+}
+
+// Position of "]" that closes "[" at the beginning of the pattern
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let mut idx = 1;
+    if matches!(pattern.get(idx), Some('!') | Some('^')) {
+        idx += 1;
+    }
+    // "]" right at the beginning is a part of the class: []abc]
+    if pattern.get(idx) == Some(&']') {
+        idx += 1;
+    }
+    while idx < pattern.len() {
+        match pattern[idx] {
+            '\\' => idx += 1,
+            ']' => return Some(idx),
+            _ => (),
+        }
+        idx += 1;
+    }
+    None
+}
+
+// Check a letter against the inside of "[...]"
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    // Get rid of slashes first, so ranges are easy to find
+    let mut letters = Vec::new();
+    let mut idx = 0;
+    while idx < class.len() {
+        if class[idx] == '\\' && idx + 1 < class.len() {
+            idx += 1;
+        }
+        letters.push(class[idx]);
+        idx += 1;
+    }
+
+    let mut found = false;
+    let mut idx = 0;
+    while idx < letters.len() {
+        if idx + 2 < letters.len() && letters[idx + 1] == '-' {
+            found |= letters[idx] <= c && c <= letters[idx + 2];
+            idx += 3;
+        } else {
+            found |= letters[idx] == c;
+            idx += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        for (pattern, text) in [
+            ("*", ""),
+            ("*", "abc"),
+            ("a*", "abc"),
+            ("*c", "abc"),
+            ("a*c", "abc"),
+            ("a**b", "ab"),
+            ("?b?", "abc"),
+            ("*ab?", "abcabd"),
+            ("m*iss*ppi", "mississippi"),
+            ("[a-c]bc", "abc"),
+            ("[!x]bc", "abc"),
+            ("[]]", "]"),
+            ("a[b", "a[b"),
+            ("a\\*c", "a*c"),
+        ] {
+            assert!(matches_text(pattern, text), "\"{pattern}\" should match \"{text}\"");
+        }
+        for (pattern, text) in [
+            ("a?", "abc"),
+            ("a*?", "a"),
+            ("[!a]bc", "abc"),
+            ("a\\*c", "axc"),
+            ("*.rs", "main.rs.bak"),
+            ("", "a"),
+        ] {
+            assert!(!matches_text(pattern, text), "\"{pattern}\" should not match \"{text}\"");
+        }
+    }
+
+    #[test]
+    fn many_stars_are_fast() {
+        let text = "a".repeat(1000);
+        assert!(!matches_text(&format!("{}b", "*a".repeat(50)), &text));
+        assert!(matches_text(&"*a".repeat(50), &text));
+    }
+}
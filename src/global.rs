@@ -1,8 +1,6 @@
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::env;
-use std::process;
-
-pub static PROGRAM_NAME: &str = "Rush";
+use serde_derive::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicUsize, Ordering};
+use crate::variables;
 
 // This indicates which command we are iterating by
 static INDEX: AtomicUsize = AtomicUsize::new(0);
@@ -11,6 +9,15 @@ static INDEX: AtomicUsize = AtomicUsize::new(0);
 pub static ALLOW_INTERRUPTS: AtomicBool = AtomicBool::new(false);
 pub static INTERRUPT_NOW: AtomicBool = AtomicBool::new(false);
 
+// Should pipelines fail when any of their commands fail?
+static PIPEFAIL: AtomicBool = AtomicBool::new(false);
+
+// Should the script stop when any command fails? Changed with "set -e" and "set +e".
+static ERREXIT: AtomicBool = AtomicBool::new(false);
+
+// Exit code of the last command that was executed
+static LAST_EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
 pub fn index() -> usize {
     INDEX.load(Ordering::SeqCst)
 }
@@ -35,85 +42,294 @@ pub fn allow_interrupts() -> bool {
     ALLOW_INTERRUPTS.load(Ordering::SeqCst)
 }
 
+pub fn set_pipefail(val: bool) {
+    PIPEFAIL.store(val, Ordering::SeqCst);
+}
+
+pub fn pipefail() -> bool {
+    PIPEFAIL.load(Ordering::SeqCst)
+}
+
+pub fn set_errexit(val: bool) {
+    ERREXIT.store(val, Ordering::SeqCst);
+}
+
+pub fn errexit() -> bool {
+    ERREXIT.load(Ordering::SeqCst)
+}
+
+pub fn set_last_exit_code(val: i32) {
+    LAST_EXIT_CODE.store(val, Ordering::SeqCst);
+}
+
+pub fn last_exit_code() -> i32 {
+    LAST_EXIT_CODE.load(Ordering::SeqCst)
+}
+
 // This function prints out an error that just occured and tells the user on which line it happened
 pub fn print_err<S:AsRef<str>>(e: S, program_name: S, line_number: usize) {
     eprintln!("Program \"{}\" returned an error at line {line_number}:\n{}", program_name.as_ref(), e.as_ref());
     set_interrupt_now(true);
 }
 
-// Remove all unescaped quotationmarks even inside words
-pub fn remove_quotationmarks<S:AsRef<str>>(input: S) -> String {
-    let mut str = String::new();
-    for c in input.as_ref().chars() {
-        // If current character is a qmark AND it's preceded by a slash, remove the slash from string
-        str.push(c);
-        let last = str.chars().last().unwrap_or(' ');
-        if (c == '\'' || c == '"') && last != '\\' {
-            str.pop();
-        }
-    }
-    str
-}
-
+#[derive(PartialEq)]
 enum ResolvingMode {
     SingleQuote,
     DoubleQuote,
-    Variable,
     None
 }
 
-// This function removes unescaped slashes
+// What to do when a script refers to a variable which does not exist
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndefinedVariables {
+    // Stop the script
+    Error,
+    // Replace it with nothing
+    Empty,
+    // Leave "$NAME" as it is
+    Keep,
+}
+
+static UNDEFINED_VARIABLES: AtomicU8 = AtomicU8::new(UndefinedVariables::Error as u8);
+
+pub fn set_undefined_variables(val: UndefinedVariables) {
+    UNDEFINED_VARIABLES.store(val as u8, Ordering::SeqCst);
+}
+
+pub fn undefined_variables() -> UndefinedVariables {
+    match UNDEFINED_VARIABLES.load(Ordering::SeqCst) {
+        x if x == UndefinedVariables::Empty as u8 => UndefinedVariables::Empty,
+        x if x == UndefinedVariables::Keep as u8 => UndefinedVariables::Keep,
+        _ => UndefinedVariables::Error,
+    }
+}
+
+// What to do with a pattern like "*.rs" that does not match any file
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmatchedGlobs {
+    // Stop the script
+    Error,
+    // Remove the word
+    Empty,
+    // Leave the pattern as it is
+    Keep,
+}
+
+static UNMATCHED_GLOBS: AtomicU8 = AtomicU8::new(UnmatchedGlobs::Keep as u8);
+
+pub fn set_unmatched_globs(val: UnmatchedGlobs) {
+    UNMATCHED_GLOBS.store(val as u8, Ordering::SeqCst);
+}
+
+pub fn unmatched_globs() -> UnmatchedGlobs {
+    match UNMATCHED_GLOBS.load(Ordering::SeqCst) {
+        x if x == UnmatchedGlobs::Error as u8 => UnmatchedGlobs::Error,
+        x if x == UnmatchedGlobs::Empty as u8 => UnmatchedGlobs::Empty,
+        _ => UnmatchedGlobs::Keep,
+    }
+}
+
+/*
+This function removes unescaped slashes and quotation marks.
+It also replaces "$NAME" and "${NAME}" with contents of a variable if "resolve_variables" is true.
+Nothing is resolved inside of single quotation marks:
+say "$HOME"     -- /home/user
+say '$HOME'     -- $HOME
+say \$HOME      -- $HOME
+
+"$(command)" is replaced with the output of a command, without new lines at the end:
+say "Today is $(date)"
+*/
 pub fn escape_slashes<S:AsRef<str>>(input: S, remove_quotation_marks:bool, resolve_variables:bool) -> Result<String, String> {
-    let mut previous_c= ' ';
-    let mut variable_name = String::new();
+    resolve(input.as_ref(), remove_quotation_marks, resolve_variables, false)
+}
+
+/*
+Same as escape_slashes with everything removed and resolved, but the result is a pattern for filename expansion.
+Only "*", "?" and "[...]" that were written without quotation marks and slashes work as wildcards.
+Letters that came from quotation marks, escaping, variables and substitutions get a slash before them:
+say "*".rs      -- \\*.rs
+say *.$EXT      -- *.rs
+*/
+pub fn glob_pattern<S:AsRef<str>>(input: S) -> Result<String, String> {
+    resolve(input.as_ref(), true, true, true)
+}
+
+// Add letters that can't be wildcards. In glob mode they are escaped.
+fn push_literal(output: &mut String, text: &str, glob: bool) {
+    for c in text.chars() {
+        if glob && ['\\', '*', '?', '[', ']'].contains(&c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+fn resolve(input: &str, remove_quotation_marks: bool, resolve_variables: bool, glob: bool) -> Result<String, String> {
     let mut output = String::new();
     let mut mode = ResolvingMode::None;
-    for c in input.as_ref().chars() {
-        if c == '\'' && previous_c != '\\' {
-            match mode {
-                ResolvingMode::None => mode = ResolvingMode::SingleQuote,
-                ResolvingMode::SingleQuote => mode = ResolvingMode::None,
-                _ => (),
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            // Letter after a slash is always left as it is
+            if let Some(escaped) = chars.next() {
+                push_literal(&mut output, &escaped.to_string(), glob);
             }
         }
-        else if c == '"' && previous_c != '\\' {
-            match mode {
-                ResolvingMode::None => mode = ResolvingMode::DoubleQuote,
-                ResolvingMode::DoubleQuote => mode = ResolvingMode::None,
-                _ => (),
+        else if c == '\'' && mode != ResolvingMode::DoubleQuote {
+            mode = if mode == ResolvingMode::None { ResolvingMode::SingleQuote } else { ResolvingMode::None };
+            if !remove_quotation_marks {
+                output.push(c);
             }
         }
-        else if c == '$' && previous_c != '\\' && resolve_variables {
-            if let ResolvingMode::None = mode { mode = ResolvingMode::Variable }
-        }
-        else if c == ' ' {
-            if let ResolvingMode::Variable = mode { mode = ResolvingMode::None }
+        else if c == '"' && mode != ResolvingMode::SingleQuote {
+            mode = if mode == ResolvingMode::None { ResolvingMode::DoubleQuote } else { ResolvingMode::None };
+            if !remove_quotation_marks {
+                output.push(c);
+            }
         }
-        else if c == '\\' && previous_c != '\\' {
-            ();
+        else if c == '$' && resolve_variables && mode != ResolvingMode::SingleQuote && chars.peek() == Some(&'(') {
+            chars.next();
+            let script = take_substitution(&mut chars)?;
+            push_literal(&mut output, &crate::substitute(&script)?, glob);
         }
-        else {
-            match mode {
-                ResolvingMode::Variable => variable_name.push(c),
-                _ => {
-                    if !variable_name.is_empty() {
-                        let env = env::var(&variable_name);
-                        match env {
-                            Err(e) => return Err(format!("{variable_name}: Reference to a variable caused an error: {:?}", e)),
-                            Ok(variable_contents) => output.push_str(&variable_contents),
+        else if c == '$' && resolve_variables && mode != ResolvingMode::SingleQuote {
+            // Variable name is either enclosed in curly brackets or it ends with the first letter
+            // that is not allowed in variable names
+            let mut variable_name = String::new();
+            let in_brackets = chars.peek() == Some(&'{');
+            if in_brackets {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(x) => variable_name.push(x),
+                        None => return Err("Missing \"}\" after \"${\"".to_string()),
+                    }
+                }
+            } else if let Some(x) = chars.next_if(|x| *x == '@' || *x == '?') {
+                // All arguments of a function or the exit code of the last command
+                variable_name.push(x);
+            } else {
+                while let Some(x) = chars.next_if(|x| x.is_alphanumeric() || *x == '_') {
+                    variable_name.push(x);
+                }
+                // Element of a list or a dictionary: $LIST[0], $DICT[key]
+                if !variable_name.is_empty() && chars.peek() == Some(&'[') {
+                    variable_name.push(chars.next().unwrap());
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(x) => variable_name.push(x),
+                            None => return Err(format!("Missing \"]\" after \"${variable_name}\"")),
+                        }
+                    }
+                    variable_name.push(']');
+                }
+            }
+
+            // Lonely dollar sign is not a variable
+            if variable_name.is_empty() && !in_brackets {
+                push_literal(&mut output, "$", glob);
+                continue;
+            }
+
+            // Positions and keys may refer to other variables too: $LIST[$IDX]
+            if let Some((name, index)) = variable_name.split_once('[') {
+                variable_name = format!("{name}[{}", escape_slashes(index, true, true)?);
+            }
+
+            // Number of elements in a list or a dictionary: ${#LIST}
+            if in_brackets && variable_name.starts_with('#') {
+                match variables::length(&variable_name[1..]) {
+                    Some(len) => push_literal(&mut output, &len.to_string(), glob),
+                    None => match undefined_variables() {
+                        UndefinedVariables::Error => return Err(format!("Variable \"{}\" is not set!", &variable_name[1..])),
+                        UndefinedVariables::Empty => (),
+                        UndefinedVariables::Keep => push_literal(&mut output, &format!("${{{variable_name}}}"), glob),
+                    },
+                }
+                continue;
+            }
+
+            match variables::get(&variable_name) {
+                Some(variable_contents) => push_literal(&mut output, &variable_contents, glob),
+                None => match undefined_variables() {
+                    UndefinedVariables::Error => return Err(format!("Variable \"{variable_name}\" is not set!")),
+                    UndefinedVariables::Empty => (),
+                    UndefinedVariables::Keep => {
+                        if in_brackets {
+                            push_literal(&mut output, &format!("${{{variable_name}}}"), glob);
+                        } else {
+                            push_literal(&mut output, &format!("${variable_name}"), glob);
                         }
-                        variable_name.clear();
                     }
-                    output.push(c);
                 },
             }
         }
-
-        if (c == '\'' || c == '"')  && !remove_quotation_marks {
+        else if mode == ResolvingMode::None {
             output.push(c);
         }
-
-        previous_c = c;
+        else {
+            push_literal(&mut output, &c.to_string(), glob);
+        }
     }
     Ok(output)
-}
\ No newline at end of file
+}
+
+/*
+Collect the text of a command substitution up to the bracket that closes it.
+"$(" has to be already taken from "chars". Brackets inside of quotation marks
+and nested substitutions are skipped: $(say "(" $(say ")"))
+*/
+pub fn take_substitution(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut script = String::new();
+    // Things that are still open. The last one decides how brackets and quotation marks are treated.
+    let mut open = vec!['('];
+    let mut after_dollar = false;
+
+    while let Some(c) = chars.next() {
+        let inside = *open.last().unwrap();
+        let dollar = after_dollar;
+        after_dollar = false;
+
+        if c == '\\' {
+            script.push(c);
+            if let Some(escaped) = chars.next() {
+                script.push(escaped);
+            }
+            continue;
+        }
+        if inside == '\'' {
+            if c == '\'' {
+                open.pop();
+            }
+        } else if c == '"' && inside == '"' {
+            open.pop();
+        } else if c == '"' || (c == '\'' && inside != '"') || (c == '(' && dollar) {
+            open.push(c);
+        } else if c == ')' && inside == '(' {
+            open.pop();
+            if open.is_empty() {
+                return Ok(script);
+            }
+        } else {
+            after_dollar = c == '$';
+        }
+        script.push(c);
+    }
+    Err("Missing \")\" after \"$(\"".to_string())
+}
+
+// Commands of a word which consists only of an unquoted substitution: $(ls)
+pub fn whole_substitution(word: &str) -> Option<String> {
+    let mut chars = word.strip_prefix("$(")?.chars();
+    let script = take_substitution(&mut chars).ok()?;
+    match chars.next() {
+        None => Some(script),
+        Some(_) => None,
+    }
+}
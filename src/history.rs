@@ -0,0 +1,170 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+/*
+Commands typed in the interactive mode are remembered in a "history" file
+which sits in the same directory as the config file.
+Every line of the file is one command. New lines and slashes inside of commands are escaped,
+so commands longer than one line can be stored too.
+*/
+
+struct History {
+    entries: Vec<String>,
+    // How many commands can be remembered. 0 means that history is disabled.
+    limit: usize,
+    file: Option<PathBuf>,
+}
+
+static HISTORY: Mutex<History> = Mutex::new(History {
+    entries: Vec::new(),
+    limit: 0,
+    file: None,
+});
+
+fn history() -> MutexGuard<'static, History> {
+    HISTORY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn file_path() -> Option<PathBuf> {
+    let config = confy::get_configuration_file_path("rush", "rush").ok()?;
+    Some(config.parent()?.join("history"))
+}
+
+// Read the history file. Nothing is remembered if it can't be found.
+pub fn init(limit: usize) {
+    let mut history = history();
+    history.limit = limit;
+    history.file = file_path();
+    history.entries = match &history.file {
+        Some(path) => fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(unescape)
+            .collect(),
+        None => Vec::new(),
+    };
+    let limit = history.limit;
+    trim(&mut history.entries, limit);
+}
+
+// Change the limit without reading the file again. Used when the config file was changed.
+pub fn set_limit(limit: usize) {
+    let mut history = history();
+    if history.limit != limit {
+        history.limit = limit;
+        trim(&mut history.entries, limit);
+        save(&history);
+    }
+}
+
+fn trim(entries: &mut Vec<String>, limit: usize) {
+    if entries.len() > limit {
+        entries.drain(..entries.len() - limit);
+    }
+}
+
+// Remember a command. Empty commands and commands that are the same as the previous one are skipped.
+pub fn add(command: &str) {
+    let mut history = history();
+    let command = command.trim();
+    if history.limit == 0
+        || command.is_empty()
+        || history.entries.last().map(String::as_str) == Some(command)
+    {
+        return;
+    }
+    history.entries.push(command.to_string());
+
+    // Usually it's enough to add a line at the end of the file.
+    // When the limit is reached, the whole file has to be written again without the oldest commands.
+    if history.entries.len() > history.limit {
+        let limit = history.limit;
+        trim(&mut history.entries, limit);
+        save(&history);
+    } else if let Some(path) = &history.file
+        && let Ok(mut file) = OpenOptions::new().append(true).create(true).open(path)
+    {
+        let _ = writeln!(file, "{}", escape(command));
+    }
+}
+
+fn save(history: &History) {
+    if let Some(path) = &history.file {
+        let mut contents = String::new();
+        for e in &history.entries {
+            contents.push_str(&escape(e));
+            contents.push('\n');
+        }
+        let _ = fs::write(path, contents);
+    }
+}
+
+// All remembered commands from the oldest to the newest
+pub fn entries() -> Vec<String> {
+    history().entries.clone()
+}
+
+fn escape(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut output = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => output.push('\n'),
+                Some(x) => output.push(x),
+                None => output.push(c),
+            }
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/*
+history             -- Show all remembered commands
+history 10          -- Show 10 newest commands
+history -s TEXT     -- Show commands containing TEXT
+history -c          -- Forget everything
+*/
+pub fn builtin(buf: &[String]) -> Result<(), String> {
+    let mut history = history();
+    let numbered = history.entries.iter().enumerate().map(|(idx, e)| (idx + 1, e));
+
+    match buf.get(1).map(String::as_str) {
+        None => {
+            for (idx, e) in numbered {
+                println!("{idx:>5}  {e}");
+            }
+        }
+        Some("-c") | Some("--clear") => {
+            history.entries.clear();
+            save(&history);
+        }
+        Some("-s") | Some("--search") => {
+            if buf.len() < 3 {
+                return Err("Give me a text to search for!".to_string());
+            }
+            let text = buf[2..].join(" ");
+            for (idx, e) in numbered.filter(|(_, e)| e.contains(&text)) {
+                println!("{idx:>5}  {e}");
+            }
+        }
+        Some(count) => {
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| format!("Number of commands to show must be a number: {count}"))?;
+            let skip = history.entries.len().saturating_sub(count);
+            for (idx, e) in numbered.skip(skip) {
+                println!("{idx:>5}  {e}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,10 +1,17 @@
+use crate::exec;
+use crate::glob;
 use crate::global;
-use crate::global::print_err;
-use std::collections::HashMap;
-use std::env;
+use crate::lexer::{self, TokenKind};
+use crate::number::{Number, is_number};
+use crate::parser;
+use crate::variables::{self, Value};
+use regex::RegexBuilder;
+use std::ffi::CString;
 use std::fmt::Debug;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::str;
-use std::process;
+use std::fs;
 
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 enum DataType {
@@ -13,15 +20,38 @@ enum DataType {
     Code,
     Out,
     Err,
+    // Checks of a file or directory
+    Exists,
+    File,
+    Dir,
+    Readable,
+    Executable,
+    Empty,
     Logic,
     Comparator,
+    // NOT and brackets that group comparisons together
+    Not,
+    OpenBracket,
+    CloseBracket,
     Var,
     Numval,
     Txtval,
     Okval,
 }
 
-pub fn logic(mut buf: Vec<String>) -> Result<bool, String> {
+pub fn logic(buf: Vec<String>) -> Result<bool, String> {
+    let tree = parse(buf)?;
+
+    /*
+    Here comes the fancy stuff!
+    Run commands inside "if comparison" and collect their exit codes.
+    Also, resolve variables to numvals or txtvals.
+    */
+    evaluate(&tree)
+}
+
+// Turn words of IF into a tree. Nothing is executed here.
+fn parse(mut buf: Vec<String>) -> Result<Expr, String> {
     /*
     if FAIL:thing1 -with-arg -with-another-arg and OK:thing2 or OUT:"thing3 and a word that should not be treated as a keyword" and $variable == 10;
         say "I'm a bunch of words on your screen"
@@ -33,68 +63,68 @@ pub fn logic(mut buf: Vec<String>) -> Result<bool, String> {
     CODE - Replace command name
     OUT - Replace command with it's stdout output
     ERR - Replace command with it's stderr output
+    EXISTS - Check if a file or a directory exists
+    FILE - Check if it is a file
+    DIR - Check if it is a directory
+    READABLE - Check if it can be read
+    EXECUTABLE - Check if it can be executed
+    EMPTY - Check if it's an empty file or a directory without anything inside
     LOGIC - AND/OR
     COMPARATOR - ==, <, >, >=, etc.
     VAR - Replace variable with it's contents
-    NUMVAL - Raw number
+    NUMVAL - Raw number. It can be negative, decimal and as long as you want: -5, 3.14, 99999999999999999999
     TXTVAL - Raw text
     OKVAL - Raw boolean
 
-    1. Make a hash map of words from IF/ELSEIF/ELSE until ";""
-       Key is a type of the thing and value is the... value.
-        > wordlist = [FAIL:thing1 -with-arg -with-another-arg, LOGIC:AND, OK:thing2, LOGIC:OR, OUT:thing3 and a word that should not be treated as a keyword, LOGIC:AND, VAR:variable, COMPARATOR:==, NUMVAR:10]
-
-    2. Iterate through wordlist
-
-    3. Look up for the first word in a list. It may be anything but LOGIC and COMPARATOR.
-       We do not accept LOGICs nor COMPARATORs at this moment because we want to prevent the user from typing something
-       like "if and thing1 ... ;" or "if == thing1 and thing2 ... ;"
-
-    4. At this point, we need a left comparable object.
-       This has to be a key of type OK, FAIL, CODE, OUT, ERR, VAL, OKVAL, NUMVAL or TXTVAL.
-       No LOGIC nor COMPARATOR is allowed.
+    NOT - Reverse the OKVAL after it
+    BRACKETS - "(" and ")" group things together
+
+    1. Make a list of words from IF/ELSEIF until ";"
+       Every element has a type of the thing and the value.
+        > wordlist = [FAIL:thing1 -with-arg -with-another-arg, LOGIC:AND, OK:thing2, LOGIC:OR, OUT:thing3 and a word that should not be treated as a keyword, LOGIC:AND, VAR:variable, COMPARATOR:==, NUMVAL:10]
+
+    2. Build a tree out of the list. Comparators are the strongest, then NOT, then AND, and OR is the weakest:
+        > A or B and C == 1             is  A or (B and (C == 1))
+        > not A == 1 and B              is  (not (A == 1)) and B
+       Brackets change the order: (A or B) and C
+       Every thing to compare must be followed by a comparator, LOGIC, ")" or the end of the statement.
+       Every comparator and LOGIC must be followed by a thing to compare.
+       Syntax errors are reported before anything is executed.
+
+    3. Compute the tree from the bottom, from left to right.
+       Things to compare are resolved only when their value is needed.
+       If the left side of AND is false or the left side of OR is true, the right side is skipped,
+       so commands in it are not executed at all: if FAIL:test -f file or OK:rm file;
        If you find a key of type OK:
         > Execute the command with all of it's arguments. Quit from IF block if it does not exist.
-        > Replace it's name depending on it's exit code.
+        > Replace it depending on it's exit code.
             If command succeeded (returned exit code 0), set it to [OKVAL:1]
             If command failed (returned any other exit code), save it as [OKVAL:0]
        If you find a key of type FAIL:
         > Do everything like in the case of OK but in reverse.
        If you find a key of type CODE, OUT or ERR:
         > Execute the command with all of it's arguments. Quit from IF block if it does not exist.
-        > Replace it's name with the value returned from stdout, stderr or an exit code.
-        > Check if it's a number or a text
-        > Replace it.
+        > Replace it with the value returned from stdout, stderr or an exit code.
             So now something like "[CODE:thing1 -with-arg -with-another-arg]" may change to "[NUMVAL:0]"
             and something like "[OUT:thing1 -with-arg -with-another-arg]" may change to "[TXTVAL:Output from thing1]"
+       If you find a key of type EXISTS, FILE, DIR, READABLE, EXECUTABLE or EMPTY:
+        > Expand the path just like a command argument. It must be exactly one path.
+        > Look at the disk without running any program and replace it with [OKVAL:1] or [OKVAL:0].
+            Things that don't exist are not files, directories, readable, executable or empty.
        If you find a key of type VAR:
         > Replace it's value with variable contents
         > Check if it's a boolean, number or a text
-        > Replace it like in example below.
             If variable is set to 10, "[VAR:variable]" will became "[NUMVAL:10]"
             if variable is set to "hello", "[VAR:variable]" will became "[TXTVAL:hello]"
             if variable is set to TRUE, "[VAR:variable]" will became "[OKVAL:1]"
             if variable is set to FALSE, "[VAR:variable]" will became "[OKVAL:0]"
-            TODO: BUT if it's set to "TRUE" or "FALSE", it will became "[TXTVAL:TRUE]" or "[TXTVAL:FALSE]"
-       Leave every OKVAL, NUMVAL and TXTVAL as is.
-    5. After exactly one CODE/OUT/ERR/VAL was translated to OKVAL, NUMVAL or TXTVAL,
-       a COMPARATOR or LOGIC is required.
-       If you reach the end of an IF comparison statement ";":
-        > Check if previous block is of type OKVAL
-        > There must be just one OKVAL block
-        > Add to a global script iteration index a value which is an index of this IF. (Do smth like INDEX += position-of-if NOT INDEX = position_of_if).
-        > Set shell_mode as CmpSuccess or CmpFailure based on the value from OKVAL
-        > End this IF statement
-       If you find a [LOGIC:AND] or [LOGIC:OR]:
-        > IF statement can't be finished yet. Report the need for another comparable thing.
-        > Check if previous block is of type OKVAL
-        > At this point, just set if_operation_mode to AND or OR
-       If you find a COMPARATOR:
-        > IF statement can't be finished yet. Report the need for another comparable thing.
-        > Check if previous block is of type NUMVAL or TXTVAL
-        > At this point, just set if_operation_mode to...
-          == and =        EQUAL (acceptable for both types)
-          !=, =! and !    DIFFERENT (acceptable for both types)
+       If you find a key of type TXTVAL:
+        > Remove quotation marks and replace variables and "$(...)" inside of it
+       Leave every OKVAL and NUMVAL as is.
+
+    4. COMPARATOR needs two things of the same type:
+          == and =        EQUAL (acceptable for all types)
+          !=, =! and !    DIFFERENT (acceptable for all types)
           <               LESS (only for NUMVALs)
           =< or <=        LESS_OR_EQUAL (only for NUMVALs)
           >               GREATER (only for NUMVALs)
@@ -102,23 +132,16 @@ pub fn logic(mut buf: Vec<String>) -> Result<bool, String> {
           ~~, =~, ~=, ~   CONTAINS (only for TXTVALs)
           *-              STARTS_WITH (only for TXTVALs)
           -*              ENDS_WITH (only for TXTVALs)
-    6. After exactly one COMPARATOR or LOGIC, another OK, FAIL, CODE, OUT, ERR, VAL, NUMVAL or TXTVAL is needed
-    7. Repeat step 4 to parse right comparable object of type OK, FAIL, CODE, OUT, ERR, VAL, NUMVAL or TXTVAL.
-
-    8. This was a right comparable object in an LEFT_CMD LOGIC/COMPARATOR RIGHT_CMD block.
-       Now, check type of this and this 2-word list.
-       and allow comparing them depending on if_operation_mode value.
-       > if_operation_mode is AND / OR - Both elements must be of type OKVAL
-       > if_operation_mode is EQUAL / DIFFERENT -  Both elements must be of type TXTVAL or NUMVAL
-       > if_operation_mode is LESS, LESS_OR..., GREATER, GREATER_OR... - Both elements must be of type NUMVAL
-       > if_operation_mode is CONTAINS - Both elements must be of type TXTVAL
-       In other case, just skip comparison.
-    10. Replace those three elements with OKVAL:1 if...
-       > if_operation_mode = AND and both comparable objects are set to OKVAL:1
-       > if_operation_mode = OR and at least one comparable object is set to OKVAL:1
-       > if_operation_mode is EQUAL/DIFFERENT/LESS/etc. and they match in type and value
-       Otherwise, replace them with OKVAL:0
-    11. Iterate through wordlist again
+          MATCHES         MATCHES (only for TXTVALs) - Regular expression on the right side.
+                          Found groups are saved in a list: $MATCH[0] is the whole match, $MATCH[1] is the first group...
+          LIKE            LIKE (only for TXTVALs) - Pattern with "*", "?" and "[...]" on the right side
+          i==, i!=, i~, i*-, i-*, IMATCHES, ILIKE - The same, but the size of letters doesn't matter
+          NEWER           NEWER (only for TXTVALs) - Path on the left was modified after the one on the right
+          OLDER           OLDER (only for TXTVALs) - Path on the left was modified before the one on the right
+                          Things that don't exist are older than anything else: if "out.o" older "main.c";
+       Comparators that work only with TXTVALs treat NUMVALs like texts.
+       AND, OR and NOT work only with OKVALs. A thing without a comparator must be an OKVAL too.
+       The whole statement is true if the top of the tree is OKVAL:1
     */
 
     /*
@@ -146,43 +169,62 @@ pub fn logic(mut buf: Vec<String>) -> Result<bool, String> {
     Join any words after CODE:program_name.
     This allows the user to just type the command they want to execute like this: CODE:something blah blah
     instead of wraping the command in quotation marks like this: CODE:"funny command here"
+    The command ends before AND, OR, a comparator or a closing bracket.
+    Prefixes are recognized only where a new thing to compare starts,
+    so words like "dir:/mnt" in "OK:docker run -v dir:/mnt img" stay a part of the command.
+    Comparators end only commands that give something to compare (CODE:, OUT: and ERR:)
+    and only when a thing to compare comes right after them.
+    So "like" in "OK:grep -q like file.txt" is just an argument of grep.
     */
-    for word in &buf {
-        if word.to_uppercase().starts_with("OK:")
-            || word.to_uppercase().starts_with("FAIL:")
-            || word.to_uppercase().starts_with("CODE:")
-            || word.to_uppercase().starts_with("OUT:")
-            || word.to_uppercase().starts_with("ERR:")
-        {
+    let words = split_brackets(buf);
+    // Type of the command that is being joined right now
+    let mut joining = None;
+    for (i, word) in words.iter().enumerate() {
+        let upper = word.to_uppercase();
+        let prefix = word
+            .split_once(':')
+            .and_then(|(prefix, rest)| Some((command_type(prefix)?, rest)))
+            .filter(|_| !append_to_last_word_instead_buf);
+        let compares = comparator(word).is_some()
+            && (!append_to_last_word_instead_buf
+                || matches!(joining, Some(DataType::Code | DataType::Out | DataType::Err))
+                    && words.get(i + 1).is_some_and(|x| starts_value(x)));
+        let ends_command = upper == "AND" || upper == "OR" || word == ")" || compares;
+
+        if let Some((datatype, rest)) = prefix {
+            // Find common errors
+            if rest.trim().is_empty() {
+                return Err(if is_file_test(&datatype) {
+                    format!("Used file check \"{}\" without specifying a path", word)
+                } else {
+                    format!(
+                        "Used command referer \"{}\" without specifying a command to run",
+                        word
+                    )
+                });
+            }
             // Push first word to the buffer
             normalized_buf.push(word.to_string());
+            joining = Some(datatype);
             // Allow appending unresolved keywords to the word instead of the buffer itself
             append_to_last_word_instead_buf = true;
         }
+        // Append it to the last word in the buffer instead of the buffer itself
+        // if it is a part of OK:, FAIL:, CODE:, OUT: or ERR: statement
+        else if append_to_last_word_instead_buf && !ends_command {
+            normalized_buf
+                .last_mut()
+                .unwrap()
+                .push_str(format!(" {}", word).as_str());
+        }
         // Just append known keywords to the buffer of commands
-        else if word.to_uppercase() == "AND"
-            || word.to_uppercase() == "OR"
-            || word == "=="
-            || word == "="
-            || word == "!"
-            || word == "!="
-            || word == "=!"
-            || word == "=<"
-            || word == "=>"
-            || word == "<="
-            || word == ">="
-            || word == "<"
-            || word == ">"
-            || word == "~"
-            || word == "~~"
-            || word == "~="
-            || word == "=~"
-            || word == "*-"
-            || word == "-*"
+        else if ends_command
+            || upper == "NOT"
+            || word == "("
             // Allow reffering to variables
             || word.starts_with('$')
             // Allow numbers too!
-            || word.parse::<usize>().is_ok()
+            || is_number(word)
             // Allow words starting with single/double quotation marks
             || word.starts_with('\'')
             || word.starts_with('"')
@@ -192,304 +234,493 @@ pub fn logic(mut buf: Vec<String>) -> Result<bool, String> {
         }
         // If you approach unknown word
         else {
-            // Append it to the last word in the buffer instead of the buffer itself
-            // if it is a part of CODE:, OUT: or ERR: statement
-            if append_to_last_word_instead_buf {
-                normalized_buf
-                    .last_mut()
-                    .unwrap()
-                    .push_str(format!(" {}", word).as_str());
-            } else {
-                return Err(format!("Unknown keyword: {word}"));
-            }
-        }
-
-        // Find common errors
-        if word.to_uppercase().trim() == "OK:"
-            || word.to_uppercase().trim() == "FAIL:"
-            || word.to_uppercase().trim() == "CODE:"
-            || word.to_uppercase().trim() == "OUT:"
-            || word.to_uppercase().trim() == "ERR:"
-        {
-            return Err(format!(
-                "Used command referer \"{}\" without specifying a command to run",
-                word
-            ));
+            return Err(format!("Unknown keyword: {word}"));
         }
     }
 
     // Make a list of all known IF arguments that is easier to understand from the
     // program's maintainer perspective :DDD
     let mut big_mommy = Vec::new();
-    for (i, w) in buf.clone().into_iter().enumerate() {
-        if w.to_uppercase().starts_with("OK:") {
-            big_mommy.push((
-                DataType::Ok,
-                buf[i].strip_prefix("OK:").unwrap().to_string(),
-            ));
-        } else if w.to_uppercase().starts_with("FAIL:") {
-            big_mommy.push((
-                DataType::Fail,
-                buf[i].strip_prefix("FAIL:").unwrap().to_string(),
-            ));
-        } else if w.to_uppercase().starts_with("CODE:") {
-            big_mommy.push((
-                DataType::Code,
-                buf[i].strip_prefix("CODE:").unwrap().to_string(),
-            ));
-        } else if w.to_uppercase().starts_with("OUT:") {
-            big_mommy.push((
-                DataType::Out,
-                buf[i].strip_prefix("OUT:").unwrap().to_string(),
-            ));
-        } else if w.to_uppercase().starts_with("ERR:") {
-            big_mommy.push((
-                DataType::Err,
-                buf[i].strip_prefix("ERR:").unwrap().to_string(),
-            ));
+    for w in normalized_buf {
+        if let Some((prefix, command)) = w.split_once(':')
+            && let Some(datatype) = command_type(prefix)
+        {
+            big_mommy.push((datatype, command.to_string()));
         } else if w.starts_with('$') {
-            big_mommy.push((DataType::Var, w.strip_prefix('$').unwrap().to_string()));
+            big_mommy.push((DataType::Var, w));
         } else if w.to_uppercase() == "AND" {
             big_mommy.push((DataType::Logic, String::from("AND")));
         } else if w.to_uppercase() == "OR" {
             big_mommy.push((DataType::Logic, String::from("OR")));
-        } else if w == "==" || w == "=" {
-            big_mommy.push((DataType::Comparator, String::from("EQUAL")));
-        } else if w == "!=" || w == "=!" || w == "!" {
-            big_mommy.push((DataType::Comparator, String::from("DIFFERENT")));
-        } else if w == "<" {
-            big_mommy.push((DataType::Comparator, String::from("LESS")));
-        } else if w == "=<" || w == "<=" {
-            big_mommy.push((DataType::Comparator, String::from("LESS_OR_EQUAL")));
-        } else if w == ">" {
-            big_mommy.push((DataType::Comparator, String::from("GREATER")));
-        } else if w == ">=" || w == "=>" {
-            big_mommy.push((DataType::Comparator, String::from("GREATER_OR_EQUAL")));
-        } else if w == "~~" || w == "~" || w == "~=" || w == "=~" {
-            big_mommy.push((DataType::Comparator, String::from("CONTAINS")));
-        } else if w == "*-" {
-            big_mommy.push((DataType::Comparator, String::from("STARTS_WITH")));
-        } else if w == "-*" {
-            big_mommy.push((DataType::Comparator, String::from("ENDS_WITH")));
+        } else if w.to_uppercase() == "NOT" {
+            big_mommy.push((DataType::Not, String::from("NOT")));
+        } else if w == "(" {
+            big_mommy.push((DataType::OpenBracket, w));
+        } else if w == ")" {
+            big_mommy.push((DataType::CloseBracket, w));
+        } else if let Some(comparator) = comparator(&w) {
+            big_mommy.push((DataType::Comparator, comparator.to_string()));
         } else {
-            let is_num = w.parse::<usize>().is_ok();
+            let is_num = is_number(&w);
             if is_num {
                 big_mommy.push((DataType::Numval, w));
             } else {
+                // Quotation marks and variables inside are resolved later, only if the text is needed
                 big_mommy.push((DataType::Txtval, w));
             }
         }
     }
 
-    // Remove unescaped quotation marks
-    let mut idx = 0;
-    while idx < big_mommy.len() {
-        big_mommy[idx].1 = global::remove_quotationmarks(&big_mommy[idx].1);
-        idx += 1;
+    // Build a tree out of the list. Check for the syntax before we do anything fancy.
+    let mut parser = Parser {
+        list: big_mommy,
+        position: 0,
+    };
+    let tree = parser.or()?;
+    if let Some((datatype, content)) = parser.list.get(parser.position) {
+        return Err(match datatype {
+            DataType::CloseBracket => "Found \")\" without \"(\" before it".to_string(),
+            _ => format!("Expected a comparator in place of \"{content}\""),
+        });
     }
+    Ok(tree)
+}
 
-    // Basically, after every thing to compare, there must be a comparator.
-    // Check for the syntax before we do anything fancy.
-    let mut idx = 0;
-    while idx < big_mommy.len() {
-        let dataunit = big_mommy[idx].clone();
+// Commands and paths can be used in IF with one of these prefixes
+fn command_type(prefix: &str) -> Option<DataType> {
+    match prefix.to_uppercase().as_str() {
+        "OK" => Some(DataType::Ok),
+        "FAIL" => Some(DataType::Fail),
+        "CODE" => Some(DataType::Code),
+        "OUT" => Some(DataType::Out),
+        "ERR" => Some(DataType::Err),
+        "EXISTS" => Some(DataType::Exists),
+        "FILE" => Some(DataType::File),
+        "DIR" => Some(DataType::Dir),
+        "READABLE" => Some(DataType::Readable),
+        "EXECUTABLE" => Some(DataType::Executable),
+        "EMPTY" => Some(DataType::Empty),
+        _ => None,
+    }
+}
 
-        if idx % 2 == 0
-            && (matches!(dataunit.0, DataType::Logic) | matches!(dataunit.0, DataType::Comparator))
-        {
-            return Err(format!(
-                "Expected a thing to compare in place of \"{}\"",
-                dataunit.1
-            ));
-        } else if idx % 2 != 0
-            && !matches!(dataunit.0, DataType::Logic)
-            && !matches!(dataunit.0, DataType::Comparator)
-        {
-            return Err(format!(
-                "Expected a comparator in place of \"{}\"",
-                dataunit.1
-            ));
-        } else {
-            //println!("{}", dataunit.1);
-        }
-        idx += 1;
+fn is_file_test(datatype: &DataType) -> bool {
+    matches!(
+        datatype,
+        DataType::Exists
+            | DataType::File
+            | DataType::Dir
+            | DataType::Readable
+            | DataType::Executable
+            | DataType::Empty
+    )
+}
+
+// Can this word start a thing to compare?
+fn starts_value(word: &str) -> bool {
+    word.starts_with('$')
+        || word.starts_with('\'')
+        || word.starts_with('"')
+        || is_number(word)
+        || word.split_once(':').is_some_and(|(prefix, _)| command_type(prefix).is_some())
+}
+
+fn comparator(word: &str) -> Option<&'static str> {
+    match word.to_lowercase().as_str() {
+        "==" | "=" => Some("EQUAL"),
+        "!=" | "=!" | "!" => Some("DIFFERENT"),
+        "<" => Some("LESS"),
+        "=<" | "<=" => Some("LESS_OR_EQUAL"),
+        ">" => Some("GREATER"),
+        ">=" | "=>" => Some("GREATER_OR_EQUAL"),
+        "~~" | "~" | "~=" | "=~" => Some("CONTAINS"),
+        "*-" => Some("STARTS_WITH"),
+        "-*" => Some("ENDS_WITH"),
+        "matches" => Some("MATCHES"),
+        "like" => Some("LIKE"),
+        // Versions that don't care about the size of letters
+        "i==" | "i=" => Some("I_EQUAL"),
+        "i!=" | "i=!" | "i!" => Some("I_DIFFERENT"),
+        "i~~" | "i~" | "i~=" | "i=~" => Some("I_CONTAINS"),
+        "i*-" => Some("I_STARTS_WITH"),
+        "i-*" => Some("I_ENDS_WITH"),
+        "imatches" => Some("I_MATCHES"),
+        "ilike" => Some("I_LIKE"),
+        // Modification times of two paths
+        "newer" => Some("NEWER"),
+        "older" => Some("OLDER"),
+        _ => None,
     }
+}
 
-    // Detect missing arguments after last comparator
-    // Example erroneous code: if 'poland' *- 'pol' and 1 = ;
-    if big_mommy.len() % 2 != 1 {
-        return Err("Expected a thing to compare at the end".to_string());
+/*
+Brackets may be glued to other words: "(OK:a or OK:b)".
+Split them, so every bracket is a separate word. Brackets in quotation marks
+and the ones that belong to "$(...)" stay where they are.
+*/
+fn split_brackets(buf: Vec<String>) -> Vec<String> {
+    let mut list = Vec::new();
+    for word in buf {
+        let mut word = word.as_str();
+        while word.len() > 1 && word.starts_with('(') {
+            list.push("(".to_string());
+            word = &word[1..];
+        }
+
+        // Count brackets outside of quotation marks to find the ones without a pair
+        let mut depth: isize = 0;
+        let mut quote = None;
+        let mut escaped = false;
+        for c in word.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '\'' | '"' if quote.is_none() => quote = Some(c),
+                _ if Some(c) == quote => quote = None,
+                '(' if quote.is_none() => depth += 1,
+                ')' if quote.is_none() => depth -= 1,
+                _ => (),
+            }
+        }
+        let mut closing = 0;
+        while depth < 0 && word.len() > 1 && word.ends_with(')') {
+            word = &word[..word.len() - 1];
+            depth += 1;
+            closing += 1;
+        }
+        list.push(word.to_string());
+        list.extend(std::iter::repeat_n(")".to_string(), closing));
     }
+    list
+}
 
-    /*
-    Here comes the fancy stuff!
-    Run commands inside "if comparison" and collect their exit codes.
-    Also, resolve variables to numvals or txtvals.
-    
-    That should be it for now.
-    */
-    loop {
-        /*
-        Always resolve commands from first and third elements in comparison statement.
-        If some of these elements are of type OUT or ERR - convert them to TXTVALs first.
-        If some of these are of type CODE - convert them to NUMVALs first.
-        If there is any environment variable reference - convert them to TXT/NUM vals too.
-
-        And also, do nothing if it's just a usual NUMVAL, TXTVAL or an OKVAL.
-        */
-
-        // Start with the first one as we are sure, that it is there.
-        match ref_to_value(big_mommy[0].clone()) {
-            Ok(res) => {
-                big_mommy.remove(0);
-                big_mommy.insert(0, res);
-            },
-            Err(e) => return Err(e),
-        };
-        
-        // If there are no other comparison statements, just quit.
-        if big_mommy.len() < 2 {
-            break
-        };
-        // Any code below is going to assume that the third element in big_mommy
-        // (which should be a second thing to compare. For instance: A number, text or a command)
-        // exists.
-
-        // Start with the first one as we are sure, that it is there.
-        match ref_to_value(big_mommy[2].clone()) {
-            Ok(res) => {
-                big_mommy.remove(2);
-                big_mommy.insert(2, res);
-            },
-            Err(e) => return Err(e),
-        };
+/*
+Comparison statement as a tree. The strongest thing is a comparator,
+then NOT, then AND, and OR is the weakest. Brackets can change that order:
+OK:a or OK:b and $x == 1        -- OK:a or (OK:b and ($x == 1))
+(OK:a or OK:b) and not $x == 1  -- (OK:a or OK:b) and (not ($x == 1))
+*/
+#[derive(Debug)]
+enum Expr {
+    // Something that has to be true or false on it's own: OK:command, $variable
+    Value((DataType, String)),
+    // Two things with a comparator between them
+    Compare((DataType, String), String, (DataType, String)),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
 
-        // Collect all the required info for future work
-        let first_value = big_mommy[0].clone();
-        let first_value_type = first_value.0;
-        let first_value_content = first_value.1;
-        let first_value_is_true = first_value_content == "1";
+struct Parser {
+    list: Vec<(DataType, String)>,
+    position: usize,
+}
 
-        let this_comparator = big_mommy[1].clone();
-        let this_comparator_content = this_comparator.1.to_uppercase();
+impl Parser {
+    fn next(&mut self) -> Option<(DataType, String)> {
+        let element = self.list.get(self.position).cloned();
+        self.position += 1;
+        element
+    }
 
-        let second_value = big_mommy[2].clone();
-        let second_value_type = second_value.0;
-        let second_value_content = second_value.1;
-        let second_value_is_true = big_mommy[2].1 == "1";
+    fn next_is(&self, datatype: DataType, content: &str) -> bool {
+        matches!(self.list.get(self.position), Some((t, c)) if *t == datatype && c == content)
+    }
 
-        // If types does not match - throw an error
-        if first_value_type != second_value_type {
-            return Err("Types of values to compare do not match!".to_string());
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.next_is(DataType::Logic, "OR") {
+            self.position += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
         }
+        Ok(left)
+    }
 
-        let replace_group_with;
-        
-        // Compare two OKVALs separated by some AND/OR logic keyword
-        if matches!(first_value_type, DataType::Okval)
-            && (this_comparator_content == "AND" || this_comparator_content == "OR")
-        {
-            let this_comparison_is_true = this_comparator_content == "AND";
-
-            replace_group_with = match (
-                first_value_is_true,
-                this_comparison_is_true,
-                second_value_is_true,
-            ) {
-                // AND
-                (true, true, true) => 1,
-                // OR
-                (true, false, true) | (false, false, true) | (true, false, false) => 1,
-                _ => 0,
-            }
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.not()?;
+        while self.next_is(DataType::Logic, "AND") {
+            self.position += 1;
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
         }
+        Ok(left)
+    }
 
-        // Compare two NUMVALs
-        else if matches!(first_value_type, DataType::Numval) {
-            let first_value_content = first_value_content.parse::<usize>().unwrap();
-            let second_value_content = second_value_content.parse::<usize>().unwrap();
-
-            replace_group_with = match this_comparator_content.as_str() {
-                "EQUAL" => (first_value_content == second_value_content).into(),
-                "DIFFERENT" => (first_value_content != second_value_content).into(),
-                "LESS" => (first_value_content < second_value_content).into(),
-                "LESS_OR_EQUAL" => (first_value_content <= second_value_content).into(),
-                "GREATER" => (first_value_content > second_value_content).into(),
-                "GREATER_OR_EQUAL" => (first_value_content >= second_value_content).into(),
-                _ => {
-                    return Err(format!(
-                        "Untolerable comparator for number values: \"{}\"",
-                        this_comparator_content
-                    ))
-                }
-            }
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.next_is(DataType::Not, "NOT") {
+            self.position += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
         }
+        self.comparison()
+    }
 
-        // Compare two TXTVALs
-        else if matches!(first_value_type, DataType::Txtval) {
-            replace_group_with = match this_comparator_content.as_str() {
-                "EQUAL" => (first_value_content == second_value_content).into(),
-                "DIFFERENT" => (first_value_content != second_value_content).into(),
-                "CONTAINS" => (first_value_content.contains(&second_value_content)).into(),
-                "STARTS_WITH" => (first_value_content.starts_with(&second_value_content)).into(),
-                "ENDS_WITH" => (first_value_content.ends_with(&second_value_content)).into(),
-                _ => {
-                    return Err(format!(
-                        "Untolerable comparator for text values: \"{}\"",
-                        this_comparator_content
-                    ))
-                }
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = match self.next() {
+            Some((DataType::OpenBracket, _)) => {
+                let inside = self.or()?;
+                return match self.next() {
+                    Some((DataType::CloseBracket, _)) => Ok(inside),
+                    Some((_, content)) => Err(format!("Expected \")\" in place of \"{content}\"")),
+                    None => Err("Missing \")\" at the end".to_string()),
+                };
             }
-        } else {
-            dbg!(
-                &big_mommy,
-                idx,
-                &big_mommy[0],
-                &big_mommy[1],
-                &big_mommy[2]
-            );
-            unreachable!("Program's logic contradics itself! Please, report this error to maintainers!\nDon't forget to share all of the debugging information above.");
+            Some(element) => operand(element)?,
+            None => return Err("Expected a thing to compare at the end".to_string()),
         };
+        if !matches!(self.list.get(self.position), Some((DataType::Comparator, _))) {
+            return Ok(Expr::Value(left));
+        }
+        let comparator = self.next().unwrap().1;
+        match self.next() {
+            Some(element) => Ok(Expr::Compare(left, comparator, operand(element)?)),
+            None => Err("Expected a thing to compare at the end".to_string()),
+        }
+    }
+}
+
+// Only things that have a value can be compared
+fn operand(element: (DataType, String)) -> Result<(DataType, String), String> {
+    match element.0 {
+        DataType::Logic
+        | DataType::Comparator
+        | DataType::Not
+        | DataType::OpenBracket
+        | DataType::CloseBracket => Err(format!(
+            "Expected a thing to compare in place of \"{}\"",
+            element.1
+        )),
+        _ => Ok(element),
+    }
+}
+
+// Commands and variables are resolved here, so the ones that are not needed are never touched
+fn evaluate(expr: &Expr) -> Result<bool, String> {
+    match expr {
+        Expr::Value(value) => match ref_to_value(value.clone())? {
+            (DataType::Okval, content) => Ok(content == "1"),
+            (_, content) => Err(format!(
+                "\"{content}\" is not true or false, so it has to be compared with something"
+            )),
+        },
+        Expr::Compare(left, comparator, right) => {
+            let left = ref_to_value(left.clone())?;
+            let right = ref_to_value(right.clone())?;
+            compare(&left, comparator, &right)
+        }
+        Expr::Not(inside) => Ok(!evaluate(inside)?),
+        Expr::And(left, right) => Ok(evaluate(left)? && evaluate(right)?),
+        Expr::Or(left, right) => Ok(evaluate(left)? || evaluate(right)?),
+    }
+}
 
-        // Remove three first elements in IF logic memory
-        big_mommy.remove(2);
-        big_mommy.remove(1);
-        big_mommy.remove(0);
-        // Insert whatever you got from previous match operation
-        big_mommy.insert(0, (DataType::Okval, replace_group_with.to_string()));
+// Comparators that only make sense for texts. Numbers are treated like texts when used with them.
+const TEXT_COMPARATORS: [&str; 14] = [
+    "CONTAINS",
+    "STARTS_WITH",
+    "ENDS_WITH",
+    "MATCHES",
+    "LIKE",
+    "I_EQUAL",
+    "I_DIFFERENT",
+    "I_CONTAINS",
+    "I_STARTS_WITH",
+    "I_ENDS_WITH",
+    "I_MATCHES",
+    "I_LIKE",
+    "NEWER",
+    "OLDER",
+];
+
+fn compare(
+    first_value: &(DataType, String),
+    comparator: &str,
+    second_value: &(DataType, String),
+) -> Result<bool, String> {
+    let (first_value_type, first_value_content) = first_value;
+    let (second_value_type, second_value_content) = second_value;
+
+    let is_text = |t: &DataType| matches!(t, DataType::Txtval | DataType::Numval);
+    if TEXT_COMPARATORS.contains(&comparator) && is_text(first_value_type) && is_text(second_value_type) {
+        return compare_texts(first_value_content, comparator, second_value_content);
+    }
+
+    // If types does not match - throw an error
+    if first_value_type != second_value_type {
+        return Err("Types of values to compare do not match!".to_string());
+    }
+
+    match first_value_type {
+        // Compare two NUMVALs
+        DataType::Numval => {
+            let first_value_content = first_value_content.parse::<Number>()?;
+            let second_value_content = second_value_content.parse::<Number>()?;
+
+            match comparator {
+                "EQUAL" => Ok(first_value_content == second_value_content),
+                "DIFFERENT" => Ok(first_value_content != second_value_content),
+                "LESS" => Ok(first_value_content < second_value_content),
+                "LESS_OR_EQUAL" => Ok(first_value_content <= second_value_content),
+                "GREATER" => Ok(first_value_content > second_value_content),
+                "GREATER_OR_EQUAL" => Ok(first_value_content >= second_value_content),
+                _ => Err(format!(
+                    "Untolerable comparator for number values: \"{}\"",
+                    comparator
+                )),
+            }
+        }
+        // Compare two TXTVALs
+        DataType::Txtval => compare_texts(first_value_content, comparator, second_value_content),
+        // Compare two OKVALs
+        DataType::Okval => match comparator {
+            "EQUAL" => Ok(first_value_content == second_value_content),
+            "DIFFERENT" => Ok(first_value_content != second_value_content),
+            _ => Err(format!(
+                "Untolerable comparator for true/false values: \"{}\"",
+                comparator
+            )),
+        },
+        _ => {
+            dbg!(first_value, comparator, second_value);
+            unreachable!("Program's logic contradicts itself! Please, report this error to maintainers!\nDon't forget to share all of the debugging information above.")
+        }
     }
+}
 
-    // After we're done with the loop
-    // check for proper type of the only one value inside of our if comparison statement
-    if big_mommy.len() == 1 && !matches!(big_mommy[0].0, DataType::Okval) {
-        dbg!(&big_mommy);
-        unreachable!("Program's logic contradicts itself! Please, report this error to maintainers!\nDon't forget to share all of the debugging information above.")
+fn compare_texts(first_value_content: &str, comparator: &str, second_value_content: &str) -> Result<bool, String> {
+    // Case insensitive comparators work on lowercase texts.
+    // Regular expressions have their own switch for that.
+    let (ignore_case, operation) = match comparator.strip_prefix("I_") {
+        Some(operation) => (true, operation),
+        None => (false, comparator),
+    };
+    match operation {
+        "MATCHES" => return regex_match(first_value_content, second_value_content, ignore_case),
+        "NEWER" => return Ok(modified(first_value_content) > modified(second_value_content)),
+        "OLDER" => return Ok(modified(first_value_content) < modified(second_value_content)),
+        _ => (),
+    }
+    let (first_value_content, second_value_content) = if ignore_case {
+        (first_value_content.to_lowercase(), second_value_content.to_lowercase())
+    } else {
+        (first_value_content.to_string(), second_value_content.to_string())
+    };
+
+    match operation {
+        "EQUAL" => Ok(first_value_content == second_value_content),
+        "DIFFERENT" => Ok(first_value_content != second_value_content),
+        "CONTAINS" => Ok(first_value_content.contains(&second_value_content)),
+        "STARTS_WITH" => Ok(first_value_content.starts_with(&second_value_content)),
+        "ENDS_WITH" => Ok(first_value_content.ends_with(&second_value_content)),
+        "LIKE" => Ok(glob::matches_text(&second_value_content, &first_value_content)),
+        _ => Err(format!(
+            "Untolerable comparator for text values: \"{}\"",
+            comparator
+        )),
     }
+}
+
+// Groups found by the regular expression are saved in MATCH, so they can be used inside of the IF block.
+// MATCH is empty when the text doesn't match.
+fn regex_match(text: &str, pattern: &str, ignore_case: bool) -> Result<bool, String> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("Wrong regular expression \"{pattern}\": {e}"))?;
+    let groups: Vec<String> = match regex.captures(text) {
+        Some(captures) => captures
+            .iter()
+            .map(|x| x.map_or(String::new(), |x| x.as_str().to_string()))
+            .collect(),
+        None => Vec::new(),
+    };
+    let matched = !groups.is_empty();
+    variables::set_value("MATCH", Value::List(groups));
+    Ok(matched)
+}
 
-    // And check if it is set to 1
-    let result_is_true = big_mommy[0].1 == "1";
-    dbg!(&big_mommy);
+// Time of the last modification. Things that don't exist have none, so they are older than anything else.
+fn modified(path: &str) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|x| x.modified()).ok()
+}
 
-    Ok(result_is_true)
+// Look at the path without running any program
+fn file_test(datatype: &DataType, path: &str) -> bool {
+    let path = Path::new(path);
+    match datatype {
+        DataType::Exists => path.exists(),
+        DataType::File => path.is_file(),
+        DataType::Dir => path.is_dir(),
+        DataType::Readable => access(path, libc::R_OK),
+        DataType::Executable => access(path, libc::X_OK),
+        DataType::Empty => match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                fs::read_dir(path).is_ok_and(|mut x| x.next().is_none())
+            }
+            Ok(metadata) => metadata.len() == 0,
+            Err(_) => false,
+        },
+        _ => false,
+    }
 }
 
+// Ask the system if we can do something with the path. This respects permissions of the user running the shell.
+fn access(path: &Path, mode: libc::c_int) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
 
 fn ref_to_value(big_mommy_element: (DataType, String)) -> Result<(DataType, String), String> {
-    let cmd = big_mommy_element.1.split_whitespace().collect::<Vec<&str>>();
-    let cmdname = cmd[0];
-    let cmdargs = cmd.iter().skip(1);
+    // Variables and values are resolved just like in any other command
+    if matches!(big_mommy_element.0, DataType::Var) {
+        let v = global::escape_slashes(&big_mommy_element.1, true, true)?;
+        return if is_number(&v) {
+            Ok((DataType::Numval, v))
+        } else if v == "TRUE" {
+            Ok((DataType::Okval, 1.to_string()))
+        } else if v == "FALSE" {
+            Ok((DataType::Okval, 0.to_string()))
+        } else {
+            Ok((DataType::Txtval, v.to_string()))
+        };
+    }
+    // Texts can have variables and "$(...)" inside, so they are resolved just like variables
+    if matches!(big_mommy_element.0, DataType::Txtval) {
+        return Ok((DataType::Txtval, global::escape_slashes(&big_mommy_element.1, true, true)?));
+    }
+    // Other raw values are left as they are
+    if !matches!(
+        big_mommy_element.0,
+        DataType::Ok | DataType::Fail | DataType::Code | DataType::Out | DataType::Err
+    ) && !is_file_test(&big_mommy_element.0)
+    {
+        return Ok(big_mommy_element);
+    }
+
+    let cmd = command_words(&big_mommy_element.1)?;
+    let cmdname = cmd[0].clone();
+
+    // Paths are expanded like arguments, but nothing is executed
+    if is_file_test(&big_mommy_element.0) {
+        if cmd.len() > 1 {
+            return Err(format!(
+                "\"{}\" is more than one path: {}",
+                big_mommy_element.1,
+                cmd.join(" ")
+            ));
+        }
+        let result = file_test(&big_mommy_element.0, &cmdname);
+        return Ok((DataType::Okval, (result as u8).to_string()));
+    }
 
     match big_mommy_element.0 {
         // If the thing's type is OK
         DataType::Ok => {
             // Run a command and collect it's exit status
-            let exit_code = process::Command::new(cmdname)
-                .args(cmdargs)
-                .status()
-                .unwrap();
+            let exit_code = exit_code(&cmd)?;
             // If the command has been ran, append a value of type OKVAL to the list of
             // IF's collection of logics
-            if let Some(code) = exit_code.code() {
+            if let Some(code) = exit_code {
                 // Is exit code a zero? Then append OKVAL:SUCCESS to the list
                 if code == 0 {
                     Ok((DataType::Okval, 1.to_string()))
@@ -505,12 +736,9 @@ fn ref_to_value(big_mommy_element: (DataType, String)) -> Result<(DataType, Stri
         }
         // This code is the exact same thing as the code above, but with reversed returns
         DataType::Fail => {
-            let exit_code = process::Command::new(cmdname)
-                .args(cmdargs)
-                .status()
-                .unwrap();
-            
-            if let Some(code) = exit_code.code() {
+            let exit_code = exit_code(&cmd)?;
+
+            if let Some(code) = exit_code {
                 if code == 0 {
                     Ok((DataType::Okval, 0.to_string()))
                 } else {
@@ -521,67 +749,173 @@ fn ref_to_value(big_mommy_element: (DataType, String)) -> Result<(DataType, Stri
             }
         }
         DataType::Code => {
-            let exit_code = process::Command::new(cmdname)
-                .args(cmdargs)
-                .status()
-                .unwrap();
-            if let Some(code) = exit_code.code() {
+            let exit_code = exit_code(&cmd)?;
+            if let Some(code) = exit_code {
                 Ok((DataType::Numval, code.to_string()))
             } else {
                 Err(format!("An error occured on command \"{}\"", cmdname))
             }
         }
-        DataType::Out => {
-            let output_message = process::Command::new(cmdname)
-                .args(cmdargs)
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .unwrap();
-
-            let mut out = String::new();
-
-            out.push_str(match str::from_utf8(&output_message.stdout) {
-                Ok(val) => val,
-                Err(_) => return Err("Got non UTF-8 data from stdout".to_string()),
-            });
-
-            Ok((DataType::Txtval, out))
+        // Outputs are compared without new lines at the end, just like in "$(...)"
+        DataType::Out | DataType::Err => {
+            let (stdout, stderr) = exec::collect(&cmd, true)?;
+
+            let stream = match big_mommy_element.0 {
+                DataType::Out => &stdout,
+                _ => &stderr,
+            };
+            let out = match str::from_utf8(stream) {
+                Ok(val) => val.trim_end_matches('\n'),
+                Err(_) => return Err("Got non UTF-8 data from the command".to_string()),
+            };
+
+            Ok((DataType::Txtval, out.to_string()))
         }
-        DataType::Err => {
-            let output_message = process::Command::new(cmdname)
-                .args(cmdargs)
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .unwrap();
-
-            let mut out = String::new();
-
-            out.push_str(match str::from_utf8(&output_message.stderr) {
-                Ok(val) => val,
-                Err(_) => return Err("Got non UTF-8 data from stdout".to_string()),
-            });
-            
-            Ok((DataType::Txtval, out))
+        _ => Ok(big_mommy_element),
+    }
+}
+
+// Split the command into words and expand them like the shell does with every other command
+fn command_words(command: &str) -> Result<Vec<String>, String> {
+    let words: Vec<parser::Word> = lexer::tokenize(command)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|t| match t.kind {
+            TokenKind::Word(text) => Some(parser::Word {
+                text,
+                line: t.line,
+                column: t.column,
+            }),
+            _ => None,
+        })
+        .collect();
+    let args = crate::expand_words(&words)?;
+    if args.is_empty() {
+        return Err(format!("\"{command}\" is not a command"));
+    }
+    Ok(args)
+}
+
+// Run a function or a program and get it's exit code.
+// Functions are run by the shell itself, so no process is spawned for them.
+// Programs are run just like any other command, so they can be stopped with CTRL+Z.
+fn exit_code(cmd: &[String]) -> Result<Option<i32>, String> {
+    if let Some(code) = crate::call_function(cmd) {
+        return Ok(Some(code));
+    }
+    exec::exec(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        std::iter::once("if")
+            .chain(text.split_whitespace())
+            .map(String::from)
+            .collect()
+    }
+
+    // Write the tree with brackets around every group
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::Value((_, content)) => content.clone(),
+            Expr::Compare((_, left), comparator, (_, right)) => format!("[{left} {comparator} {right}]"),
+            Expr::Not(inside) => format!("(not {})", shape(inside)),
+            Expr::And(left, right) => format!("({} and {})", shape(left), shape(right)),
+            Expr::Or(left, right) => format!("({} or {})", shape(left), shape(right)),
         }
-        DataType::Var => {
-            let variable = env::var(&big_mommy_element.1);
-            if let Ok(v) = variable {
-                let num = v.parse::<usize>();
-                if let Ok(result) = num {
-                    Ok((DataType::Numval, result.to_string()))
-                } else if v == "TRUE" {
-                    Ok((DataType::Okval, 1.to_string()))
-                } else if v == "FALSE" {
-                    Ok((DataType::Okval, 0.to_string()))
-                } else {
-                    Ok((DataType::Txtval, v.to_string()))
-                }
-            } else {
-                Err(format!("Variable \"{}\" is undefined", &big_mommy_element.1))
-            }
+    }
+
+    fn parsed(text: &str) -> String {
+        shape(&parse(words(text)).unwrap())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            parsed("not 1 == 2 and 3 == 3 or 4 == 5"),
+            "(((not [1 EQUAL 2]) and [3 EQUAL 3]) or [4 EQUAL 5])"
+        );
+        assert_eq!(
+            parsed("OK:a or OK:b and $x == 1"),
+            "(a or (b and [$x EQUAL 1]))"
+        );
+        assert_eq!(
+            parsed("OK:a and OK:b and OK:c or OK:d"),
+            "(((a and b) and c) or d)"
+        );
+        assert_eq!(parsed("not not OK:a"), "(not (not a))");
+    }
+
+    #[test]
+    fn brackets() {
+        assert_eq!(
+            parsed("(OK:a or OK:b) and not $x == 1"),
+            "((a or b) and (not [$x EQUAL 1]))"
+        );
+        assert_eq!(
+            parsed("not ( OK:a and ( OK:b or OK:c ) )"),
+            "(not (a and (b or c)))"
+        );
+        assert_eq!(parsed("((OK:a))"), "a");
+    }
+
+    #[test]
+    fn commands_take_following_words() {
+        assert_eq!(
+            parsed("OK:docker run -v dir:/mnt img and DIR:/tmp"),
+            "(docker run -v dir:/mnt img and /tmp)"
+        );
+        assert_eq!(
+            parsed("OUT:say a b == \"ab\""),
+            "[say a b EQUAL \"ab\"]"
+        );
+        // Comparators are a part of the command when nothing could be compared with them
+        assert_eq!(
+            parsed("OK:grep -q like file.txt and OK:test a = b"),
+            "(grep -q like file.txt and test a = b)"
+        );
+        assert_eq!(
+            parsed("OUT:grep like file.txt like \"*x*\""),
+            "[grep like file.txt LIKE \"*x*\"]"
+        );
+        assert_eq!(parsed("CODE:ls older == 1"), "[ls older EQUAL 1]");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        for text in [
+            "1 == 1 and",
+            "( 1 == 1",
+            "1 == 1 )",
+            "1 ==",
+            "== 1",
+            "1 == 1 2",
+            "OK:a and or OK:b",
+            "()",
+            "DIR:",
+            "word",
+        ] {
+            assert!(parse(words(text)).is_err(), "\"{text}\" should not be parsed");
         }
-        _ => Ok(big_mommy_element),
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn short_circuit() {
+        // Right side would fail if it was resolved
+        assert_eq!(logic(words("1 == 2 and $RUSH_TEST_UNDEFINED == 1")), Ok(false));
+        assert_eq!(logic(words("1 == 1 or $RUSH_TEST_UNDEFINED == 1")), Ok(true));
+        assert_eq!(logic(words("1 == 2 and \"$RUSH_TEST_UNDEFINED\" == \"a\"")), Ok(false));
+        assert!(logic(words("1 == 1 and $RUSH_TEST_UNDEFINED == 1")).is_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(logic(words("-2 < -1.5 and 2.50 == 2.5")), Ok(true));
+        assert_eq!(logic(words("\"abc\" like \"a*\" and \"ABC\" i== \"abc\"")), Ok(true));
+        assert_eq!(logic(words("not ( 1 == 1 or 1 == 2 )")), Ok(false));
+        assert!(logic(words("1 == \"a\"")).is_err());
+    }
+}
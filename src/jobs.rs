@@ -0,0 +1,473 @@
+use crate::global::{interrupt_now, pipefail, set_interrupt_now};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::{thread, time};
+
+/*
+Every pipeline is a job. Jobs started with "&" at the end are kept in a list of jobs,
+so they can be checked with "jobs", brought back with "fg", continued with "bg", etc.
+
+In the interactive mode every job gets it's own process group and the terminal is given to
+the job which runs in the foreground. Thanks to that, CTRL+C and CTRL+Z are sent only to that job
+and not to the shell or jobs in the background.
+*/
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Running,
+    Stopped,
+    // Exit code or 128 + number of a signal that killed the process
+    Done(i32),
+}
+
+struct Process {
+    pid: i32,
+    status: Status,
+}
+
+impl Process {
+    // Save the status returned by waitpid()
+    fn record(&mut self, status: i32) {
+        if libc::WIFEXITED(status) {
+            self.status = Status::Done(libc::WEXITSTATUS(status));
+        } else if libc::WIFSIGNALED(status) {
+            self.status = Status::Done(128 + libc::WTERMSIG(status));
+        } else if libc::WIFSTOPPED(status) {
+            self.status = Status::Stopped;
+        } else if libc::WIFCONTINUED(status) {
+            self.status = Status::Running;
+        }
+    }
+}
+
+pub struct Job {
+    id: usize,
+    // Process group of the job. There are no groups when job control is disabled.
+    pgid: Option<i32>,
+    processes: Vec<Process>,
+    command: String,
+}
+
+static JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+// Is the shell in charge of the terminal?
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+// Copy of the terminal's file descriptor
+static TERMINAL: AtomicI32 = AtomicI32::new(-1);
+
+fn jobs() -> MutexGuard<'static, Vec<Job>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn job_control() -> bool {
+    JOB_CONTROL.load(Ordering::SeqCst)
+}
+
+pub fn terminal() -> i32 {
+    TERMINAL.load(Ordering::SeqCst)
+}
+
+// Take control over the terminal. This is done only in the interactive mode.
+pub fn init_job_control() {
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) == 0 {
+            return;
+        }
+        // CTRL+Z and reading from the terminal in the background should never stop the shell itself
+        libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+        libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+        libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+
+        let terminal = libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 10);
+        if terminal < 0 {
+            return;
+        }
+        // Shell has to be in it's own process group to give the terminal to others and take it back
+        libc::setpgid(0, 0);
+        libc::tcsetpgrp(terminal, libc::getpgrp());
+        TERMINAL.store(terminal, Ordering::SeqCst);
+    }
+    JOB_CONTROL.store(true, Ordering::SeqCst);
+}
+
+// Things done by a new process before it starts a program. Signals ignored by the shell
+// would be ignored by every program too, so they have to be set to defaults.
+pub fn prepare_child(foreground: bool) {
+    unsafe {
+        if foreground {
+            libc::tcsetpgrp(terminal(), libc::getpgrp());
+        }
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::signal(libc::SIGTTIN, libc::SIG_DFL);
+        libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+    }
+}
+
+impl Job {
+    pub fn new(pgid: Option<i32>, pids: Vec<i32>, command: String) -> Self {
+        Self {
+            id: 0,
+            pgid,
+            processes: pids
+                .into_iter()
+                .map(|pid| Process {
+                    pid,
+                    status: Status::Running,
+                })
+                .collect(),
+            command,
+        }
+    }
+
+    fn status(&self) -> Status {
+        if self.processes.iter().any(|p| p.status == Status::Stopped) {
+            Status::Stopped
+        } else if self.processes.iter().any(|p| p.status == Status::Running) {
+            Status::Running
+        } else {
+            Status::Done(self.exit_code())
+        }
+    }
+
+    // Exit code of the last command. If "pipefail" is enabled, exit code of the last command that failed.
+    fn exit_code(&self) -> i32 {
+        let mut codes = self.processes.iter().rev().filter_map(|p| match p.status {
+            Status::Done(code) => Some(code),
+            _ => None,
+        });
+        if pipefail() {
+            codes.find(|c| *c != 0).unwrap_or(0)
+        } else {
+            codes.next().unwrap_or(0)
+        }
+    }
+
+    // Check what happened with processes of the job without waiting for them
+    fn update(&mut self) {
+        for p in &mut self.processes {
+            if matches!(p.status, Status::Done(_)) {
+                continue;
+            }
+            let mut status = 0;
+            let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+            let result = unsafe { libc::waitpid(p.pid, &mut status, flags) };
+            if result < 0 {
+                // Somebody else took care of this process
+                p.status = Status::Done(0);
+            } else if result > 0 {
+                p.record(status);
+            }
+        }
+    }
+
+    /*
+    Sleep until one of the processes finishes or gets stopped.
+    CTRL+C doesn't stop the waiting. The job gets SIGINT by itself and it decides what to do with it.
+    */
+    fn wait(&mut self) {
+        let target = match self.pgid {
+            Some(pgid) => -pgid,
+            None => match self.processes.iter().find(|p| p.status == Status::Running) {
+                Some(p) => p.pid,
+                None => return,
+            },
+        };
+        let mut status = 0;
+        let result = unsafe { libc::waitpid(target, &mut status, libc::WUNTRACED) };
+        if result > 0 {
+            if let Some(p) = self.processes.iter_mut().find(|p| p.pid == result) {
+                p.record(status);
+            }
+        } else if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            // There is nothing left to wait for
+            for p in &mut self.processes {
+                if p.status == Status::Running {
+                    p.status = Status::Done(0);
+                }
+            }
+        }
+    }
+
+    fn signal(&self, signal: i32) -> Result<(), String> {
+        let result = match self.pgid {
+            Some(pgid) => unsafe { libc::kill(-pgid, signal) },
+            None => self
+                .processes
+                .iter()
+                .filter(|p| !matches!(p.status, Status::Done(_)))
+                .map(|p| unsafe { libc::kill(p.pid, signal) })
+                .min()
+                .unwrap_or(0),
+        };
+        if result < 0 {
+            Err(format!(
+                "Failed to send a signal: {:?}",
+                std::io::Error::last_os_error().kind()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn describe(&self) -> String {
+        let status = match self.status() {
+            Status::Running => "Running".to_string(),
+            Status::Stopped => "Stopped".to_string(),
+            Status::Done(0) => "Done".to_string(),
+            Status::Done(code) => format!("Exit {code}"),
+        };
+        format!("[{}]  {status:<10}{}", self.id, self.command)
+    }
+}
+
+// Give the terminal to the job (or take it back when pgid is None)
+fn give_terminal(pgid: Option<i32>) {
+    if !job_control() {
+        return;
+    }
+    unsafe {
+        libc::tcsetpgrp(terminal(), pgid.unwrap_or_else(|| libc::getpgrp()));
+    }
+}
+
+/*
+Wait until the job finishes or gets stopped with CTRL+Z.
+Stopped jobs are added to the list of jobs, so they can be continued later.
+Returns an exit code of the job.
+*/
+pub fn foreground(job: Job) -> i32 {
+    // Same as a process stopped by SIGTSTP
+    wait_foreground(job).unwrap_or(128 + libc::SIGTSTP)
+}
+
+// Just like foreground(), but returns None when the job was stopped
+pub fn wait_foreground(mut job: Job) -> Option<i32> {
+    // Programs like text editors change settings of the terminal. Bring them back after the job is done.
+    let mut modes: libc::termios = unsafe { std::mem::zeroed() };
+    let saved_modes = job_control() && unsafe { libc::tcgetattr(terminal(), &mut modes) } == 0;
+    give_terminal(job.pgid);
+
+    let status = loop {
+        match job.status() {
+            Status::Running => job.wait(),
+            status => break status,
+        }
+    };
+
+    give_terminal(None);
+    if saved_modes {
+        unsafe {
+            libc::tcsetattr(terminal(), libc::TCSADRAIN, &modes);
+        }
+    }
+
+    match status {
+        Status::Stopped => {
+            let id = add(job);
+            eprintln!();
+            with_job(id, |job| eprintln!("{}", job.describe()));
+            None
+        }
+        _ => {
+            // CTRL+C stopped the job, so the rest of the script should stop too
+            if job
+                .processes
+                .iter()
+                .any(|p| p.status == Status::Done(128 + libc::SIGINT))
+            {
+                set_interrupt_now(true);
+            }
+            Some(job.exit_code())
+        }
+    }
+}
+
+// Remember a job that runs in the background. Returns it's number.
+// Jobs that were already on the list keep their old numbers.
+fn add(mut job: Job) -> usize {
+    let mut jobs = jobs();
+    if job.id == 0 {
+        job.id = jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+    }
+    let id = job.id;
+    jobs.push(job);
+    id
+}
+
+pub fn background(job: Job) {
+    let pid = job.pgid.or(job.processes.last().map(|p| p.pid)).unwrap_or(0);
+    let id = add(job);
+    eprintln!("[{id}] {pid}");
+}
+
+fn with_job<T>(id: usize, f: impl FnOnce(&mut Job) -> T) -> Option<T> {
+    jobs().iter_mut().find(|j| j.id == id).map(f)
+}
+
+// Take a job out of the list
+fn take(id: usize) -> Option<Job> {
+    let mut jobs = jobs();
+    let idx = jobs.iter().position(|j| j.id == id)?;
+    Some(jobs.remove(idx))
+}
+
+// Show jobs that finished since the last time and forget about them
+pub fn notify() {
+    let mut jobs = jobs();
+    for job in jobs.iter_mut() {
+        job.update();
+        if matches!(job.status(), Status::Done(_)) {
+            eprintln!("{}", job.describe());
+        }
+    }
+    jobs.retain(|j| !matches!(j.status(), Status::Done(_)));
+}
+
+// Find out which job the user means: "%2", "2" or nothing for the newest one
+fn job_id(buf: &[String]) -> Result<usize, String> {
+    match buf.get(1) {
+        Some(id) => {
+            let id = id.strip_prefix('%').unwrap_or(id);
+            let id = id
+                .parse::<usize>()
+                .map_err(|_| format!("Job number must be a number: {id}"))?;
+            if with_job(id, |_| ()).is_none() {
+                return Err(format!("There is no job with number {id}"));
+            }
+            Ok(id)
+        }
+        None => jobs()
+            .last()
+            .map(|j| j.id)
+            .ok_or_else(|| "There are no jobs".to_string()),
+    }
+}
+
+// jobs - Show all jobs started from the shell
+pub fn list(buf: &[String]) -> Result<(), String> {
+    if buf.len() > 1 {
+        return Err("This command does not accept any arguments!".to_string());
+    }
+    let mut jobs = jobs();
+    for job in jobs.iter_mut() {
+        job.update();
+        println!("{}", job.describe());
+    }
+    jobs.retain(|j| !matches!(j.status(), Status::Done(_)));
+    Ok(())
+}
+
+// fg [%ID] - Continue the job in the foreground and wait for it
+pub fn fg(buf: &[String]) -> Result<i32, String> {
+    let id = job_id(buf)?;
+    let mut job = take(id).unwrap();
+    eprintln!("{}", job.command);
+    job.signal(libc::SIGCONT)?;
+    for p in &mut job.processes {
+        if p.status == Status::Stopped {
+            p.status = Status::Running;
+        }
+    }
+    Ok(foreground(job))
+}
+
+// bg [%ID] - Continue stopped job in the background
+pub fn bg(buf: &[String]) -> Result<(), String> {
+    let id = job_id(buf)?;
+    with_job(id, |job| {
+        job.signal(libc::SIGCONT)?;
+        eprintln!("[{}] {} &", job.id, job.command);
+        Ok(())
+    })
+    .unwrap()
+}
+
+// wait [%ID] - Wait until the job finishes. Without any arguments, wait for all of them.
+pub fn wait(buf: &[String]) -> Result<i32, String> {
+    let ids: Vec<usize> = if buf.len() > 1 {
+        vec![job_id(buf)?]
+    } else {
+        jobs().iter().map(|j| j.id).collect()
+    };
+
+    let mut code = 0;
+    for id in ids {
+        let mut job = take(id).unwrap();
+        loop {
+            job.update();
+            match job.status() {
+                Status::Done(c) => {
+                    code = c;
+                    break;
+                }
+                // Stopped jobs would never finish
+                Status::Stopped => {
+                    code = 128 + libc::SIGTSTP;
+                    add(job);
+                    break;
+                }
+                Status::Running => (),
+            }
+            if interrupt_now() {
+                add(job);
+                return Ok(130);
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+    Ok(code)
+}
+
+/*
+kill [-SIGNAL] %ID|PID...
+Signal can be a number or a name like TERM, KILL, INT, STOP, CONT. TERM is used by default.
+*/
+pub fn kill(buf: &[String]) -> Result<(), String> {
+    let mut targets = &buf[1..];
+    let mut signal = libc::SIGTERM;
+    if let Some(name) = targets.first().and_then(|x| x.strip_prefix('-')) {
+        signal = signal_number(name)?;
+        targets = &targets[1..];
+    }
+    if targets.is_empty() {
+        return Err("Give me a job or a process to send a signal to!".to_string());
+    }
+
+    for t in targets {
+        if let Some(id) = t.strip_prefix('%') {
+            let id = job_id(&["kill".to_string(), id.to_string()])?;
+            with_job(id, |job| job.signal(signal)).unwrap()?;
+        } else {
+            let pid = t
+                .parse::<i32>()
+                .map_err(|_| format!("Process ID must be a number: {t}"))?;
+            if unsafe { libc::kill(pid, signal) } < 0 {
+                return Err(format!(
+                    "{pid}: Failed to send a signal: {:?}",
+                    std::io::Error::last_os_error().kind()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn signal_number(name: &str) -> Result<i32, String> {
+    if let Ok(num) = name.parse::<i32>() {
+        return Ok(num);
+    }
+    let name = name.to_uppercase();
+    Ok(match name.strip_prefix("SIG").unwrap_or(&name) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "TSTP" => libc::SIGTSTP,
+        _ => return Err(format!("Unknown signal: {name}")),
+    })
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+/*
+Lexer turns the text of a script into a list of tokens.
+
+Words keep their original text, so quotation marks and escaping slashes are still there.
+They are removed later, when the command is going to be executed, because some parts of the
+shell (like IF) need to know if the word was enquoted or not.
+
+Possible tokens are:
+WORD - Anything that is not a whitespace or a separator. Words in quotation marks may contain spaces, commas and new lines.
+SEPARATOR - Unquoted comma or a new line character. It ends a command.
+SEMICOLON - Unquoted semicolon. It ends a comparison in IF/ELSEIF.
+
+Command substitution "$(...)" is a part of the word too, even if there are spaces or quotation marks inside of it.
+Commas inside of unquoted curly brackets don't end a command, so braces can be expanded later: say {a,b}
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(String),
+    Separator,
+    Semicolon,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    // Where does the token start in the script
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    // Script ended too early, so more lines could fix it. Like in: lock, say hi
+    pub unfinished: bool,
+}
+
+impl SyntaxError {
+    pub fn new<S: AsRef<str>>(message: S, line: usize, column: usize) -> Self {
+        Self {
+            message: message.as_ref().to_string(),
+            line,
+            column,
+            unfinished: false,
+        }
+    }
+
+    pub fn unfinished<S: AsRef<str>>(message: S, line: usize, column: usize) -> Self {
+        Self {
+            unfinished: true,
+            ..Self::new(message, line, column)
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+// Things that are opened inside of a word and have to be closed before the word ends
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Single,
+    Double,
+    // $(...)
+    Substitution,
+}
+
+pub fn tokenize<S: AsRef<str>>(script: S) -> Result<Vec<Token>, SyntaxError> {
+    let mut tokens = Vec::new();
+
+    // Word that we are currently collecting letters for and the place where it starts
+    let mut word = String::new();
+    let mut word_start = (1, 1);
+    // Quotation marks and substitutions that are still open with places where they start.
+    // Used to report unclosed ones.
+    let mut contexts: Vec<(Context, (usize, usize))> = Vec::new();
+
+    let mut escaped = false;
+    // Was the previous letter an unescaped dollar sign?
+    let mut after_dollar = false;
+    // Unquoted curly brackets opened in the current word. Commas inside of them are a part of the word: file.{rs,toml}
+    let mut braces = 0;
+
+    let mut line = 1;
+    let mut column = 0;
+
+    for c in script.as_ref().chars() {
+        column += 1;
+
+        // Remember where the word starts
+        if word.is_empty() {
+            word_start = (line, column);
+            braces = 0;
+        }
+
+        let context = contexts.last().map(|x| x.0);
+        let dollar = after_dollar;
+        after_dollar = false;
+
+        // Letters after unescaped slashes are always a part of the word
+        // Slash at the end of a line joins it with the next line
+        if escaped && c == '\n' && context != Some(Context::Single) {
+            word.pop();
+            escaped = false;
+        } else if escaped {
+            word.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            word.push(c);
+            escaped = true;
+        } else if context == Some(Context::Single) {
+            if c == '\'' {
+                contexts.pop();
+            }
+            word.push(c);
+        } else if c == '\'' && context != Some(Context::Double) {
+            contexts.push((Context::Single, (line, column)));
+            word.push(c);
+        } else if c == '"' && context == Some(Context::Double) {
+            contexts.pop();
+            word.push(c);
+        } else if c == '"' {
+            contexts.push((Context::Double, (line, column)));
+            word.push(c);
+        } else if c == '(' && dollar {
+            contexts.push((Context::Substitution, (line, column - 1)));
+            word.push(c);
+        } else if c == ')' && context == Some(Context::Substitution) {
+            contexts.pop();
+            word.push(c);
+        } else if context.is_some() {
+            after_dollar = c == '$';
+            word.push(c);
+        } else if c == ' ' || c == '\t' || c == '\r' {
+            end_word(&mut tokens, &mut word, word_start);
+        } else if c == '\n' || (c == ',' && braces == 0) {
+            end_word(&mut tokens, &mut word, word_start);
+            tokens.push(Token {
+                kind: TokenKind::Separator,
+                line,
+                column,
+            });
+        } else if c == ';' {
+            end_word(&mut tokens, &mut word, word_start);
+            tokens.push(Token {
+                kind: TokenKind::Semicolon,
+                line,
+                column,
+            });
+        } else {
+            match c {
+                '{' => braces += 1,
+                '}' if braces > 0 => braces -= 1,
+                _ => (),
+            }
+            after_dollar = c == '$';
+            word.push(c);
+        }
+
+        if c == '\n' {
+            line += 1;
+            column = 0;
+        }
+    }
+
+    if let Some((context, (line, column))) = contexts.last() {
+        let message = match context {
+            Context::Substitution => "Unclosed \"$(\"",
+            _ => "Unclosed quotation mark",
+        };
+        return Err(SyntaxError::unfinished(message, *line, *column));
+    }
+    if escaped {
+        return Err(SyntaxError::unfinished(
+            "Nothing to escape after \"\\\"",
+            line,
+            column,
+        ));
+    }
+    end_word(&mut tokens, &mut word, word_start);
+
+    Ok(tokens)
+}
+
+// Add collected word to the list of tokens
+fn end_word(tokens: &mut Vec<Token>, word: &mut String, word_start: (usize, usize)) {
+    if !word.is_empty() {
+        tokens.push(Token {
+            kind: TokenKind::Word(std::mem::take(word)),
+            line: word_start.0,
+            column: word_start.1,
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/*
+Number used in comparisons by IF and CMP.
+It can be negative, have a fractional part and any number of digits: -12, 3.14, 123456789012345678901234567890
+Numbers are compared digit by digit, so there are no rounding errors, even when integers are compared with decimals.
+*/
+#[derive(Debug, Clone)]
+pub struct Number {
+    negative: bool,
+    // Digits before the dot, without zeros at the beginning
+    integer: String,
+    // Digits after the dot, without zeros at the end
+    fraction: String,
+}
+
+impl FromStr for Number {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || format!("\"{text}\" is not a number");
+        let (negative, unsigned) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if (integer.is_empty() && fraction.is_empty())
+            || !integer.chars().chain(fraction.chars()).all(|x| x.is_ascii_digit())
+        {
+            return Err(error());
+        }
+
+        let integer = integer.trim_start_matches('0').to_string();
+        let fraction = fraction.trim_end_matches('0').to_string();
+        // There is no such thing as a negative zero
+        let negative = negative && !(integer.is_empty() && fraction.is_empty());
+        Ok(Self {
+            negative,
+            integer,
+            fraction,
+        })
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Longer integer part is always bigger. Fractional parts can be compared like texts.
+        let magnitude = self
+            .integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(&other.integer))
+            .then_with(|| self.fraction.cmp(&other.fraction));
+        match (self.negative, other.negative) {
+            (false, false) => magnitude,
+            (true, true) => magnitude.reverse(),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+pub fn is_number(text: &str) -> bool {
+    text.parse::<Number>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(text: &str) -> Number {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn equal() {
+        for (a, b) in [
+            ("-0", "0"),
+            ("0.0", "-0.000"),
+            ("1.10", "1.1"),
+            ("007", "7"),
+            ("+3", "3"),
+            (".5", "0.5"),
+            ("5.", "5"),
+        ] {
+            assert_eq!(number(a), number(b), "{a} == {b}");
+        }
+    }
+
+    #[test]
+    fn order() {
+        for (smaller, bigger) in [
+            ("-2", "-1.5"),
+            ("-10", "-9.99"),
+            ("-0.5", "0.25"),
+            ("-1", "0"),
+            ("0.09", "0.1"),
+            ("2.5", "3"),
+            ("9", "10"),
+            ("99.999", "100"),
+            ("18446744073709551615", "18446744073709551616"),
+            ("-123456789012345678901234567891", "-123456789012345678901234567890"),
+            ("123456789012345678901234567890", "123456789012345678901234567890.000001"),
+        ] {
+            assert!(number(smaller) < number(bigger), "{smaller} < {bigger}");
+            assert!(number(bigger) > number(smaller), "{bigger} > {smaller}");
+        }
+    }
+
+    #[test]
+    fn not_numbers() {
+        for text in ["", "-", "+", ".", "1.2.3", "abc", "1e3", "--1", "1-", " 1", "0x10"] {
+            assert!(!is_number(text), "\"{text}\" is not a number");
+        }
+    }
+}
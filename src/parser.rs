@@ -0,0 +1,723 @@
+use crate::lexer::{SyntaxError, Token, TokenKind};
+
+/*
+Parser builds a tree of commands and blocks (AST) out of the tokens returned by the lexer.
+
+if $a == 1;                 -- IF node with a condition "$a == 1"
+    say hello               -- COMMAND node inside of the first IF branch
+elseif $a == 2;             -- Next IF branch with it's own condition
+    lock                    -- LOCK node inside of the second IF branch
+        say forever         -- COMMAND node inside of the LOCK
+    endlock
+else                        -- Commands that run when no condition was met
+    free                    -- FREE node. Will be rejected by the syntax checker, because it's not inside of a LOCK
+endif
+
+Keywords are only recognized at the beginning of a command and they are not case sensitive.
+*/
+
+#[derive(Debug, Clone)]
+pub struct Word {
+    // Original text of the word with quotation marks and slashes
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectionKind {
+    // > file
+    Output,
+    // >> file
+    Append,
+    // < file
+    Input,
+    // 2> file
+    Error,
+    // 2>> file
+    ErrorAppend,
+    // 2>&1
+    ErrorToOutput,
+}
+
+// Operators are checked in this order, so longer ones have to be first
+const REDIRECTIONS: [(&str, RedirectionKind); 6] = [
+    ("2>&1", RedirectionKind::ErrorToOutput),
+    ("2>>", RedirectionKind::ErrorAppend),
+    ("2>", RedirectionKind::Error),
+    (">>", RedirectionKind::Append),
+    (">", RedirectionKind::Output),
+    ("<", RedirectionKind::Input),
+];
+
+#[derive(Debug, Clone)]
+pub struct Redirection {
+    pub kind: RedirectionKind,
+    // File name. There is none for 2>&1
+    pub target: Option<Word>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub words: Vec<Word>,
+    pub redirections: Vec<Redirection>,
+    pub line: usize,
+}
+
+// Commands connected with "|". Single command is also a pipeline.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    // "&" at the end runs the pipeline in the background
+    pub background: bool,
+    pub line: usize,
+}
+
+// Operators between pipelines: say hello && say bye || say error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChainKind {
+    // "&&" - Run the next pipeline only when the previous one succeeded
+    And,
+    // "||" - Run the next pipeline only when the previous one failed
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForMethod {
+    PerSign,
+    PerWord,
+    PerLine,
+    In,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Pipeline(Pipeline),
+    Chain {
+        first: Pipeline,
+        rest: Vec<(ChainKind, Pipeline)>,
+        line: usize,
+    },
+    If {
+        // Every IF and ELSEIF with words from the comparison statement and commands to run
+        branches: Vec<(Vec<Word>, Vec<Node>)>,
+        // Commands from ELSE
+        otherwise: Option<Vec<Node>>,
+        line: usize,
+    },
+    Lock {
+        body: Vec<Node>,
+        line: usize,
+    },
+    For {
+        switches: Vec<Word>,
+        // Current iteration referer
+        variable: Word,
+        method: ForMethod,
+        list: Vec<Word>,
+        body: Vec<Node>,
+        line: usize,
+    },
+    Func {
+        name: Word,
+        body: Vec<Node>,
+        line: usize,
+    },
+    Free(usize),
+    Continue(usize),
+    Return {
+        // Exit code of the function. Zero is used when it's missing.
+        code: Option<Word>,
+        line: usize,
+    },
+}
+
+impl Node {
+    pub fn line(&self) -> usize {
+        match self {
+            Node::Pipeline(p) => p.line,
+            Node::Chain { line, .. }
+            | Node::If { line, .. }
+            | Node::Lock { line, .. }
+            | Node::For { line, .. }
+            | Node::Func { line, .. }
+            | Node::Return { line, .. } => *line,
+            Node::Free(line) | Node::Continue(line) => *line,
+        }
+    }
+}
+
+// Blocks that have to be closed with an ending keyword
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Builtins {
+    Lock,
+    If,
+    For,
+    Func,
+}
+
+impl Builtins {
+    fn name(&self) -> &'static str {
+        match self {
+            Builtins::Lock => "LOCK",
+            Builtins::If => "IF",
+            Builtins::For => "FOR",
+            Builtins::Func => "FUNC",
+        }
+    }
+}
+
+pub fn parse(tokens: Vec<Token>) -> Result<Vec<Node>, SyntaxError> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+    let (nodes, _) = parser.parse_block(None)?;
+    Ok(nodes)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    // Get a word at current position
+    fn word(&self) -> Option<Word> {
+        match self.tokens.get(self.position) {
+            Some(Token {
+                kind: TokenKind::Word(text),
+                line,
+                column,
+            }) => Some(Word {
+                text: text.clone(),
+                line: *line,
+                column: *column,
+            }),
+            _ => None,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(t) = self.tokens.get(self.position) {
+            if matches!(t.kind, TokenKind::Word(_)) {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    // Collect words until the end of a command
+    fn words_until_separator(&mut self) -> Vec<Word> {
+        let mut words = Vec::new();
+        while let Some(w) = self.word() {
+            let operator = ["|", "&&", "||"].contains(&w.text.as_str());
+            words.push(w);
+            self.position += 1;
+            // Pipeline can be continued in the next line: say hello |
+            if operator {
+                self.skip_separators();
+            }
+        }
+        words
+    }
+
+    // Make sure that nothing else follows a keyword like ENDIF or FREE
+    fn expect_end_of_command(&self, keyword: &Word) -> Result<(), SyntaxError> {
+        match self.word() {
+            Some(w) => Err(SyntaxError::new(
+                format!(
+                    "Unexpected word \"{}\" after \"{}\"",
+                    w.text,
+                    keyword.text.to_uppercase()
+                ),
+                w.line,
+                w.column,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Parse commands until the end of the script or until the keyword which closes the block we are inside.
+    // The closing keyword is returned, so the caller knows what to do next (ELSEIF, ELSE, ENDIF).
+    fn parse_block(
+        &mut self,
+        inside: Option<(Builtins, &Word)>,
+    ) -> Result<(Vec<Node>, Option<Word>), SyntaxError> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_separators();
+            let Some(word) = self.word() else {
+                return match inside {
+                    Some((builtin, opener)) => Err(SyntaxError::unfinished(
+                        format!("Unclosed \"{}\" statement", builtin.name()),
+                        opener.line,
+                        opener.column,
+                    )),
+                    None => Ok((nodes, None)),
+                };
+            };
+
+            let keyword = word.text.to_lowercase();
+            match keyword.as_str() {
+                "if" => nodes.push(self.parse_if()?),
+                "lock" => nodes.push(self.parse_lock()?),
+                "for" => nodes.push(self.parse_for()?),
+                "func" => nodes.push(self.parse_func()?),
+                "return" => {
+                    self.position += 1;
+                    let code = self.word();
+                    if let Some(code) = &code {
+                        self.position += 1;
+                        self.expect_end_of_command(code)?;
+                    }
+                    nodes.push(Node::Return {
+                        code,
+                        line: word.line,
+                    });
+                }
+                "free" | "continue" => {
+                    self.position += 1;
+                    self.expect_end_of_command(&word)?;
+                    if keyword == "free" {
+                        nodes.push(Node::Free(word.line));
+                    } else {
+                        nodes.push(Node::Continue(word.line));
+                    }
+                }
+                "elseif" | "else" | "endif" | "endlock" | "end" | "endfunc" => {
+                    let closes = match keyword.as_str() {
+                        "endlock" => Builtins::Lock,
+                        "end" => Builtins::For,
+                        "endfunc" => Builtins::Func,
+                        _ => Builtins::If,
+                    };
+                    if matches!(inside, Some((builtin, _)) if builtin == closes) {
+                        self.position += 1;
+                        return Ok((nodes, Some(word)));
+                    }
+                    return Err(SyntaxError::new(
+                        format!(
+                            "Usage of \"{}\" outside of the \"{}\" statement is incorrect",
+                            keyword.to_uppercase(),
+                            closes.name()
+                        ),
+                        word.line,
+                        word.column,
+                    ));
+                }
+                _ => nodes.push(self.parse_chain()?),
+            }
+        }
+    }
+
+    // Split words of a command into pipelines wherever "&&" or "||" is found
+    fn parse_chain(&mut self) -> Result<Node, SyntaxError> {
+        let words = self.words_until_separator();
+        let line = words[0].line;
+
+        let mut pipelines: Vec<(Option<ChainKind>, Vec<Word>)> = vec![(None, Vec::new())];
+        for w in words {
+            let kind = match w.text.as_str() {
+                "&&" => ChainKind::And,
+                "||" => ChainKind::Or,
+                _ => {
+                    pipelines.last_mut().unwrap().1.push(w);
+                    continue;
+                }
+            };
+            if pipelines.last().unwrap().1.is_empty() {
+                return Err(SyntaxError::new(
+                    format!("Missing a command before \"{}\"", w.text),
+                    w.line,
+                    w.column,
+                ));
+            }
+            pipelines.push((Some(kind), Vec::new()));
+        }
+        if pipelines.last().unwrap().1.is_empty() {
+            let w = self.tokens.last().unwrap();
+            return Err(SyntaxError::unfinished(
+                "Missing a command after \"&&\" or \"||\"",
+                w.line,
+                w.column,
+            ));
+        }
+
+        let count = pipelines.len();
+        let mut parsed = Vec::new();
+        for (idx, (kind, words)) in pipelines.into_iter().enumerate() {
+            let pipeline = parse_pipeline(words)?;
+            if pipeline.background && idx < count - 1 {
+                return Err(SyntaxError::new(
+                    "\"&\" can only be used at the end of a command",
+                    pipeline.line,
+                    1,
+                ));
+            }
+            parsed.push((kind, pipeline));
+        }
+
+        let mut parsed = parsed.into_iter();
+        let first = parsed.next().unwrap().1;
+        if count == 1 {
+            return Ok(Node::Pipeline(first));
+        }
+        Ok(Node::Chain {
+            first,
+            rest: parsed.map(|(kind, p)| (kind.unwrap(), p)).collect(),
+            line,
+        })
+    }
+
+    // Words between IF/ELSEIF and a semicolon
+    fn parse_condition(&mut self, keyword: &Word) -> Result<Vec<Word>, SyntaxError> {
+        let condition = self.words_until_separator();
+        if condition.is_empty() {
+            return Err(SyntaxError::new(
+                format!(
+                    "Missing comparison statement after \"{}\"",
+                    keyword.text.to_uppercase()
+                ),
+                keyword.line,
+                keyword.column,
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn parse_if(&mut self) -> Result<Node, SyntaxError> {
+        let opener = self.word().unwrap();
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+
+        self.position += 1;
+        let condition = self.parse_condition(&opener)?;
+        let (body, mut terminator) = self.parse_block(Some((Builtins::If, &opener)))?;
+        branches.push((condition, body));
+
+        // Block ends with ELSEIF, ELSE or ENDIF. Do something different in every case.
+        while let Some(keyword) = terminator {
+            match keyword.text.to_lowercase().as_str() {
+                "elseif" | "else" if otherwise.is_some() => {
+                    return Err(SyntaxError::new(
+                        format!(
+                            "\"{}\" can't be used after \"ELSE\"",
+                            keyword.text.to_uppercase()
+                        ),
+                        keyword.line,
+                        keyword.column,
+                    ));
+                }
+                "elseif" => {
+                    let condition = self.parse_condition(&keyword)?;
+                    let (body, next) = self.parse_block(Some((Builtins::If, &opener)))?;
+                    branches.push((condition, body));
+                    terminator = next;
+                }
+                "else" => {
+                    let (body, next) = self.parse_block(Some((Builtins::If, &opener)))?;
+                    otherwise = Some(body);
+                    terminator = next;
+                }
+                _ => {
+                    self.expect_end_of_command(&keyword)?;
+                    terminator = None;
+                }
+            }
+        }
+
+        Ok(Node::If {
+            branches,
+            otherwise,
+            line: opener.line,
+        })
+    }
+
+    fn parse_lock(&mut self) -> Result<Node, SyntaxError> {
+        let opener = self.word().unwrap();
+        self.position += 1;
+        let (body, terminator) = self.parse_block(Some((Builtins::Lock, &opener)))?;
+        self.expect_end_of_command(&terminator.unwrap())?;
+        Ok(Node::Lock {
+            body,
+            line: opener.line,
+        })
+    }
+
+    /*
+    func greet
+        say "Hello $1!"
+        return 0
+    endfunc
+    */
+    fn parse_func(&mut self) -> Result<Node, SyntaxError> {
+        let opener = self.word().unwrap();
+        self.position += 1;
+
+        let Some(name) = self.word() else {
+            return Err(SyntaxError::new(
+                "Missing function name after \"FUNC\"",
+                opener.line,
+                opener.column,
+            ));
+        };
+        if !name
+            .text
+            .chars()
+            .all(|x| x.is_alphanumeric() || x == '_' || x == '-')
+        {
+            return Err(SyntaxError::new(
+                format!("Function name contains invalid characters: {}", name.text),
+                name.line,
+                name.column,
+            ));
+        }
+        self.position += 1;
+        self.expect_end_of_command(&name)?;
+
+        let (body, terminator) = self.parse_block(Some((Builtins::Func, &opener)))?;
+        self.expect_end_of_command(&terminator.unwrap())?;
+
+        Ok(Node::Func {
+            name,
+            body,
+            line: opener.line,
+        })
+    }
+
+    /*
+    for -s=2 a perword $LIST do
+        say "$a"
+    end
+    */
+    fn parse_for(&mut self) -> Result<Node, SyntaxError> {
+        let opener = self.word().unwrap();
+        self.position += 1;
+
+        // Collect everything until "DO"
+        let mut header = Vec::new();
+        loop {
+            match self.word() {
+                Some(w) if w.text.to_lowercase() == "do" => {
+                    self.position += 1;
+                    break;
+                }
+                Some(w) => {
+                    header.push(w);
+                    self.position += 1;
+                }
+                // More words may come in the next line
+                None if self.position >= self.tokens.len() => {
+                    return Err(SyntaxError::unfinished(
+                        "Missing \"DO\" operator inside a \"FOR\" statement",
+                        opener.line,
+                        opener.column,
+                    ));
+                }
+                None => {
+                    return Err(SyntaxError::new(
+                        "Missing \"DO\" operator inside a \"FOR\" statement",
+                        opener.line,
+                        opener.column,
+                    ));
+                }
+            }
+        }
+
+        // Switches need to be defined first. Even before an iteration referer
+        let switches: Vec<Word> = header
+            .iter()
+            .take_while(|w| w.text.starts_with('-'))
+            .cloned()
+            .collect();
+        let mut rest = header.into_iter().skip(switches.len());
+
+        let Some(variable) = rest.next() else {
+            return Err(SyntaxError::new(
+                "Missing current iteration referer inside a \"FOR\" statement",
+                opener.line,
+                opener.column,
+            ));
+        };
+        let method = match rest.next() {
+            Some(w) => match w.text.to_lowercase().as_str() {
+                "persign" => ForMethod::PerSign,
+                "perword" => ForMethod::PerWord,
+                "perline" => ForMethod::PerLine,
+                "in" => ForMethod::In,
+                _ if w.text.starts_with('-') => {
+                    return Err(SyntaxError::new(
+                        "Switches for the \"FOR\" block have to be defined first",
+                        w.line,
+                        w.column,
+                    ));
+                }
+                _ => {
+                    return Err(SyntaxError::new(
+                        "Separation method must be defined after current iteration reference",
+                        w.line,
+                        w.column,
+                    ));
+                }
+            },
+            None => {
+                return Err(SyntaxError::new(
+                    "Missing separation method inside a \"FOR\" statement",
+                    variable.line,
+                    variable.column,
+                ));
+            }
+        };
+        let list: Vec<Word> = rest.collect();
+        if list.is_empty() {
+            return Err(SyntaxError::new(
+                "Missing a list to iterate through inside a \"FOR\" statement",
+                opener.line,
+                opener.column,
+            ));
+        }
+
+        let (body, terminator) = self.parse_block(Some((Builtins::For, &opener)))?;
+        self.expect_end_of_command(&terminator.unwrap())?;
+
+        Ok(Node::For {
+            switches,
+            variable,
+            method,
+            list,
+            body,
+            line: opener.line,
+        })
+    }
+}
+
+// Split words of a command into separate commands wherever "|" is found
+fn parse_pipeline(mut words: Vec<Word>) -> Result<Pipeline, SyntaxError> {
+    let line = words[0].line;
+
+    let background = words.last().is_some_and(|w| w.text == "&");
+    if background {
+        let w = words.pop().unwrap();
+        if words.is_empty() {
+            return Err(SyntaxError::new(
+                "Missing a command before \"&\"",
+                w.line,
+                w.column,
+            ));
+        }
+    }
+    if let Some(w) = words.iter().find(|w| w.text == "&") {
+        return Err(SyntaxError::new(
+            "\"&\" can only be used at the end of a command",
+            w.line,
+            w.column,
+        ));
+    }
+
+    let mut commands = Vec::new();
+    // Remember the last "|" to show where a command is missing
+    let mut last_pipe = None;
+
+    let mut group: Vec<Word> = Vec::new();
+    for w in words {
+        if w.text == "|" {
+            if group.is_empty() {
+                return Err(SyntaxError::new(
+                    "Missing a command before \"|\"",
+                    w.line,
+                    w.column,
+                ));
+            }
+            commands.push(parse_redirections(std::mem::take(&mut group))?);
+            last_pipe = Some(w);
+        } else {
+            group.push(w);
+        }
+    }
+    if group.is_empty() {
+        let w = last_pipe.unwrap();
+        return Err(SyntaxError::unfinished(
+            "Missing a command after \"|\"",
+            w.line,
+            w.column,
+        ));
+    }
+    commands.push(parse_redirections(group)?);
+
+    Ok(Pipeline {
+        commands,
+        background,
+        line,
+    })
+}
+
+/*
+Take redirections out of command's words.
+File name can be written right after the operator or as a separate word:
+say hello >file.txt
+say hello > file.txt
+*/
+fn parse_redirections(group: Vec<Word>) -> Result<Command, SyntaxError> {
+    let (line, column) = (group[0].line, group[0].column);
+    let mut words = Vec::new();
+    let mut redirections = Vec::new();
+
+    let mut group = group.into_iter();
+    while let Some(w) = group.next() {
+        let Some((operator, kind)) = REDIRECTIONS
+            .iter()
+            .find(|(operator, _)| w.text.starts_with(operator))
+        else {
+            words.push(w);
+            continue;
+        };
+
+        let rest = &w.text[operator.len()..];
+        let target = if *kind == RedirectionKind::ErrorToOutput {
+            if !rest.is_empty() {
+                return Err(SyntaxError::new(
+                    format!("Unexpected text \"{rest}\" after \"{operator}\""),
+                    w.line,
+                    w.column,
+                ));
+            }
+            None
+        } else if !rest.is_empty() {
+            Some(Word {
+                text: rest.to_string(),
+                line: w.line,
+                column: w.column + operator.len(),
+            })
+        } else {
+            match group.next() {
+                Some(target) => Some(target),
+                None => {
+                    return Err(SyntaxError::new(
+                        format!("Missing a file name after \"{operator}\""),
+                        w.line,
+                        w.column,
+                    ));
+                }
+            }
+        };
+        redirections.push(Redirection {
+            kind: *kind,
+            target,
+        });
+    }
+
+    if words.is_empty() {
+        return Err(SyntaxError::new(
+            "Missing a command to redirect",
+            line,
+            column,
+        ));
+    }
+
+    Ok(Command {
+        words,
+        redirections,
+        line,
+    })
+}
@@ -0,0 +1,127 @@
+use crate::global::last_exit_code;
+use crate::{directories, variables};
+use std::ffi;
+use std::path::Path;
+
+/*
+Prompt from the config file may contain special sequences that are replaced every time it's shown:
+\w - Current working directory (home directory is shown as "~")
+\W - Name of the current directory only
+\u - User name
+\h - Host name
+\? - Exit code of the last command
+\t - Current time (HH:MM:SS)
+\n - New line
+\\ - Slash
+\{color} - Change color of the text after it. Possible colors are: black, red, green, yellow, blue,
+           magenta, cyan, white, bold and reset
+Example: prompt = "\{green}\u@\h\{reset}:\{blue}\w\{reset} [\?] > "
+*/
+pub fn render(prompt: &str) -> String {
+    let mut output = String::new();
+    let mut chars = prompt.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => output.push_str(&current_dir(false)),
+            Some('W') => output.push_str(&current_dir(true)),
+            Some('u') => output.push_str(&user()),
+            Some('h') => output.push_str(&host()),
+            Some('?') => output.push_str(&last_exit_code().to_string()),
+            Some('t') => output.push_str(&time()),
+            Some('n') => output.push('\n'),
+            Some('\\') => output.push('\\'),
+            Some('{') => {
+                let name: String = chars.by_ref().take_while(|x| *x != '}').collect();
+                match color(&name) {
+                    Some(code) => output.push_str(code),
+                    // Show unknown colors as they are, so the user can see what's wrong
+                    None => output.push_str(&format!("\\{{{name}}}")),
+                }
+            }
+            Some(x) => {
+                output.push('\\');
+                output.push(x);
+            }
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+fn color(name: &str) -> Option<&'static str> {
+    Some(match name.to_lowercase().as_str() {
+        "black" => "\x1b[30m",
+        "red" => "\x1b[31m",
+        "green" => "\x1b[32m",
+        "yellow" => "\x1b[33m",
+        "blue" => "\x1b[34m",
+        "magenta" => "\x1b[35m",
+        "cyan" => "\x1b[36m",
+        "white" => "\x1b[37m",
+        "bold" => "\x1b[1m",
+        "reset" => "\x1b[0m",
+        _ => return None,
+    })
+}
+
+// Directory changed with "gt" is the working directory of the shell
+fn current_dir(only_name: bool) -> String {
+    let dir = directories::current();
+    if only_name {
+        return match Path::new(&dir).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => dir,
+        };
+    }
+    // Paths are compared by their parts, so "/home/user2" is not inside of "/home/user"
+    let home = variables::get("HOME").filter(|x| !x.is_empty());
+    match home.as_ref().and_then(|x| Path::new(&dir).strip_prefix(x).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.to_string_lossy()),
+        None => dir,
+    }
+}
+
+fn user() -> String {
+    if let Some(name) = variables::get("USER") {
+        return name;
+    }
+    // USER is not always set, so ask the system about the owner of the shell
+    unsafe {
+        let passwd = libc::getpwuid(libc::geteuid());
+        if passwd.is_null() {
+            return "?".to_string();
+        }
+        ffi::CStr::from_ptr((*passwd).pw_name)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn host() -> String {
+    let mut buf = [0 as libc::c_char; 256];
+    unsafe {
+        if libc::gethostname(buf.as_mut_ptr(), buf.len() - 1) != 0 {
+            return "?".to_string();
+        }
+        ffi::CStr::from_ptr(buf.as_ptr())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn time() -> String {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return "??:??:??".to_string();
+        }
+        format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
@@ -1,22 +1,33 @@
 use carrot_libs::args;
-use dialoguer;
-use global::remove_quotationmarks;
 use global::escape_slashes;
 use std::fs;
-use std::env;
 use std::process;
 use std::thread;
+mod aliases;
+mod completion;
 mod config;
 mod directories;
+mod editor;
 mod exec;
+mod expansion;
+mod functions;
+mod glob;
 mod global;
+mod history;
+mod jobs;
 mod r#if;
+mod lexer;
+mod number;
+mod parser;
+mod prompt;
 mod variables;
 
 use config::RushConfig;
+use parser::{ChainKind, Node};
 use global::{
-    allow_interrupts, index, interrupt_now, print_err, set_allow_interrupts, set_index,
-    set_interrupt_now,
+    UnmatchedGlobs, allow_interrupts, errexit, index, interrupt_now, last_exit_code, print_err,
+    set_allow_interrupts, set_errexit, set_index, set_interrupt_now, set_last_exit_code,
+    set_pipefail, set_undefined_variables, set_unmatched_globs, unmatched_globs,
 };
 
 fn main() {
@@ -43,31 +54,58 @@ fn main() {
         }
     });
 
+    // Shell keeps it's own copy of variables from the environment
+    variables::init();
+    directories::init();
+    // Aliases are read only once. Config is loaded again before every command,
+    // but aliases added with ALIAS would be lost then.
+    aliases::init(config::load().aliases);
+
     // Collect words in whole script
     // from interactive console or a file
 
     // If we have no options, run in interactive mode
     // Start text entry thing and wait for the user to type the command
     if opts.is_empty() {
+        history::init(config::load().history_size);
+        jobs::init_job_control();
+        // Lines typed so far when a command needs more of them
+        let mut script = String::new();
         loop {
             set_index(0);
             set_allow_interrupts(true);
             set_interrupt_now(false);
-            let cfg: RushConfig = match confy::load("rush", "rush") {
-                Err(e) => {
-                    eprintln!("Failed to read config file: {}!", e);
-                    process::exit(1)
-                }
-                Ok(e) => e,
-            };
-
-            let cmd: Result<String, dialoguer::Error> = dialoguer::Input::new().interact_text();
+            let cfg: RushConfig = config::load();
+            apply_config(&cfg);
+            history::set_limit(cfg.history_size);
+            // Tell the user about jobs that finished in the background
+            jobs::notify();
 
-            match cmd {
-                Ok(e) => {
-                    do_rest_of_magic_or_nothing(
-                        e.split_whitespace().map(|x| x.to_string()).collect(),
-                    );
+            let prompt = if script.is_empty() {
+                &cfg.prompt
+            } else {
+                &cfg.continuation_prompt
+            };
+            match editor::read_line(&prompt::render(prompt)) {
+                Ok(editor::Input::Line(e)) => {
+                    script.push_str(&e);
+                    // Wait for ENDLOCK, closing quotation mark, etc.
+                    if is_unfinished(&script) {
+                        script.push('\n');
+                        continue;
+                    }
+                    // Blocks typed in many lines are remembered as one command
+                    history::add(&script);
+                    do_rest_of_magic_or_nothing(std::mem::take(&mut script));
+                }
+                Ok(editor::Input::Cancelled) => script.clear(),
+                // CTRL+D
+                Ok(editor::Input::Quit) => {
+                    if script.is_empty() {
+                        process::exit(last_exit_code());
+                    }
+                    // Show what is missing
+                    do_rest_of_magic_or_nothing(std::mem::take(&mut script));
                 }
                 Err(e) => {
                     eprintln!("Can't get user input: {e}");
@@ -78,14 +116,21 @@ fn main() {
     }
     // If there are some options, read the file to the 'script' vector
     else {
+        let cfg: RushConfig = config::load();
+        apply_config(&cfg);
         for o in opts {
             set_index(0);
             set_allow_interrupts(true);
             set_interrupt_now(false);
+            // "set -e" from one script does not change other scripts
+            set_errexit(false);
             match fs::read_to_string(o) {
-                Ok(e) => do_rest_of_magic_or_nothing(
-                    e.split_whitespace().map(|x| x.to_string()).collect(),
-                ),
+                Ok(e) => {
+                    // Variables set by one script are not visible in other scripts
+                    variables::push_scope();
+                    do_rest_of_magic_or_nothing(e);
+                    variables::pop_scope();
+                }
                 Err(e) => {
                     eprintln!("Unable to read from script file: {:?}", e.kind());
                     process::exit(1);
@@ -95,137 +140,42 @@ fn main() {
     };
 }
 
-fn do_rest_of_magic_or_nothing(script: Vec<String>) {
-    // Do nothing if script is empty
-    if script.is_empty() {
-        print!("");
-        return;
-    }
-    group_quotationmarks(script);
+// Make options from the config file visible for the rest of the shell
+fn apply_config(cfg: &RushConfig) {
+    set_pipefail(cfg.pipefail);
+    set_undefined_variables(cfg.undefined_variables);
+    set_unmatched_globs(cfg.unmatched_globs);
+    directories::set_cdpath(cfg.cdpath.clone());
 }
 
-/*
-This function joins separate arguments enclosed in single or double quotationmarks.
-For example:
-- "this would be tolerated as one argument"
-- 'this is a big argument, too'
-- th"is is also just o"ne
-- "this is incorrect'
-- this won't be accepted either
-- that\'s good
-- this sentence represents multiple arguments. no quotationmarks!
-*/
-fn group_quotationmarks(script: Vec<String>) {
-    // Collect non-quoted and joined quoted commands from the script here
-    let mut buf = Vec::new();
-    let mut words_in_qmarks = String::new();
-
-    let mut single_qmarks = false;
-    let mut double_qmarks = false;
-
-    for w in script {
-        // Iterate through all the letters to find quotationmarks
-        for (c_idx, c) in w.chars().enumerate() {
-            // Toggle single/double_qmark variable if we find a quotationmark that is not
-            // enclosed in other quotationmark AND is not preceded by a slash.
-            if c == '\''
-                && (c_idx == 0 || w.chars().nth(c_idx - 1).unwrap() != '\\')
-                && !double_qmarks
-            {
-                single_qmarks = !single_qmarks;
-            }
+// Check if the script ends too early, so it could be fixed by typing more lines
+fn is_unfinished(script: &str) -> bool {
+    matches!(lexer::tokenize(script).and_then(parser::parse), Err(e) if e.unfinished)
+}
 
-            if c == '"'
-                && (c_idx == 0 || w.chars().nth(c_idx - 1).unwrap() != '\\')
-                && !single_qmarks
-            {
-                double_qmarks = !double_qmarks;
-            }
-        }
-        if single_qmarks || double_qmarks {
-            if !words_in_qmarks.is_empty() {
-                words_in_qmarks.push(' ');
-            };
-            words_in_qmarks.push_str(&w);
-        } else if !words_in_qmarks.is_empty() {
-            words_in_qmarks.push(' ');
-            words_in_qmarks.push_str(&w);
-            buf.push(words_in_qmarks.clone());
-            words_in_qmarks.clear();
-        } else {
-            buf.push(w.clone());
-        }
+fn do_rest_of_magic_or_nothing(script: String) {
+    // Do nothing if script is empty
+    if script.trim().is_empty() {
+        return;
     }
 
-    syntax_test(buf);
-}
+    // Split the script into words and separators, then build a tree of commands out of them
+    let ast = match lexer::tokenize(&script).and_then(parser::parse) {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("There are errors in your script that need to be fixed or they can cause serious issues!");
+            eprintln!("\t{e}");
+            return;
+        }
+    };
 
-enum Builtins {
-    Lock,
-    If,
+    syntax_test(ast);
 }
 
-fn syntax_test(script: Vec<String>) {
-    let mut line_number = 1;
-    let mut used_builtins_history = Vec::new();
-
+fn syntax_test(script: Vec<Node>) {
     // Build list of errors to show
     let mut errors = Vec::new();
-
-    // Iterate through every word in script and catch some common errors
-    for w in &script {
-        // Errors messages that tell the user where problematic code is, are much more readable c;
-        if w.ends_with('\n') {
-            line_number += 1
-        };
-
-        // Catch usage of logical statements
-        if w.to_lowercase() == "lock" {
-            used_builtins_history.push(Builtins::Lock);
-        };
-        if w.to_lowercase() == "if" {
-            used_builtins_history.push(Builtins::If);
-        };
-
-        // Any logical statements have to be ended with associated ending keywords like ENDLOCK or ENDIF
-        // If you find it somewhere, remove the last logical statement from history
-        if w.to_lowercase().trim() == "endlock" || w.to_lowercase().trim() == "endlock;" {
-            match used_builtins_history.last() {
-                Some(Builtins::Lock) => {used_builtins_history.pop();},
-                _ => errors.push(format!("{line_number}: Usage of \"ENDLOCK\" outside of the \"LOCK\" statement is incorrect")),
-            }
-        }
-        if w.to_lowercase().trim() == "endif" || w.to_lowercase().trim() == "endif;" {
-            match used_builtins_history.last() {
-                Some(Builtins::If) => {used_builtins_history.pop();},
-                _ => errors.push(format!("{line_number}: Usage of \"ENDIF\" outside of the \"IF\" statement is incorrect")),
-            }
-        }
-
-        // Catch use of free or continue
-        if (w == "free" || w == "continue")
-            && !used_builtins_history
-                .iter()
-                .any(|x| matches!(x, Builtins::Lock))
-        {
-            errors.push(format!("{line_number}: Usage of \"FREE\" or \"CONTINUE\" is not permited outside of the \"LOCK\" statement"));
-        }
-
-        // Disallow running empty commands like this: say hello; ; say bye
-        // if w == ";" {
-        //     errors.push(format!("{line_number}: Trying to run empty command!"))
-        // }
-    }
-
-    // Summarize looking for unclosed logical statements
-    if !used_builtins_history.is_empty() {
-        for element in used_builtins_history {
-            match element {
-                Builtins::Lock => errors.push(("Unclosed \"LOCK\" statement").to_string()),
-                Builtins::If => errors.push(("Unclosed \"IF\" statement").to_string()),
-            }
-        }
-    }
+    find_errors(&script, false, false, &mut errors);
 
     // Show errors
     if !errors.is_empty() {
@@ -239,7 +189,50 @@ fn syntax_test(script: Vec<String>) {
     make_script_thread(script);
 }
 
-fn make_script_thread(script: Vec<String>) {
+// Walk through every block in script and catch some common errors
+// Unclosed blocks are already catched by the parser
+fn find_errors(block: &[Node], inside_lock: bool, inside_func: bool, errors: &mut Vec<String>) {
+    for node in block {
+        match node {
+            Node::If {
+                branches,
+                otherwise,
+                ..
+            } => {
+                for (_, body) in branches {
+                    find_errors(body, inside_lock, inside_func, errors);
+                }
+                if let Some(body) = otherwise {
+                    find_errors(body, inside_lock, inside_func, errors);
+                }
+            }
+            Node::Lock { body, .. } => find_errors(body, true, inside_func, errors),
+            // FREE and CONTINUE can't reach LOCKs from outside of a function
+            Node::Func { body, .. } => find_errors(body, false, true, errors),
+            Node::For { body, line, .. } => {
+                // Prevent from running for loop without tasks in it
+                // Example: "for a perword $LIST do end"
+                if body.is_empty() {
+                    errors.push(format!("{line}: \"FOR\" block is missing tasks to run"));
+                }
+                find_errors(body, true, inside_func, errors);
+            }
+            Node::Free(line) | Node::Continue(line) => {
+                if !inside_lock {
+                    errors.push(format!("{line}: Usage of \"FREE\" or \"CONTINUE\" is not permited outside of the \"LOCK\" or \"FOR\" statement"));
+                }
+            }
+            Node::Return { line, .. } => {
+                if !inside_func {
+                    errors.push(format!("{line}: Usage of \"RETURN\" is not permited outside of the \"FUNC\" statement"));
+                }
+            }
+            Node::Pipeline(_) | Node::Chain { .. } => (),
+        }
+    }
+}
+
+fn make_script_thread(script: Vec<Node>) {
     // Allow responses for SIGINT
     set_allow_interrupts(true);
 
@@ -252,37 +245,534 @@ fn make_script_thread(script: Vec<String>) {
     set_allow_interrupts(false);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum ShellMode {
     // Run all commands inside IF/ELSEIF/ELSE block
     CmpSuccess,
-    // Skip tasks inside current IF/ELSEIF/ELSE block and try running another one
-    CmpFailure,
-    // When shell mode is set to CmpSuccess, skip checking any other IF/ELSEIF/ELSE and included subcommands
-    // IfDone status will be reset by ENDIF keywords
+    // Skip remaining commands inside current IF/ELSEIF/ELSE block
+    // Used when FREE or CONTINUE was found inside of it
     IfDone,
-    // After you reach "endlock", go back to LOCK defined in position_of_lock.
-    // Allow usage of BREAK and CONTNUE
+    // After you reach the end of the block, go back to the beginning of the LOCK.
+    // Allow usage of FREE and CONTNUE
     Lock,
     // Skip executing commands until you reach ENDLOCK. Go back to LOCK.
     LockContinue,
     // Skip executing commands until you reach ENDLOCK. But do not go back to LOCK.
     LockFree,
+    // Run all commands inside of a function
+    Func,
+    // Skip remaining commands of a function. It returns an exit code.
+    FuncReturn(i32),
 }
 
 struct ShellModes {
-    list: Vec<ShellModes>
+    list: Vec<ShellMode>,
+}
+
+impl ShellModes {
+    fn new() -> Self {
+        Self { list: Vec::new() }
+    }
+
+    // Commands are skipped when any of the blocks we are in is not supposed to run
+    fn skipping(&self) -> bool {
+        self.list.iter().any(|x| {
+            matches!(
+                x,
+                ShellMode::IfDone
+                    | ShellMode::LockContinue
+                    | ShellMode::LockFree
+                    | ShellMode::FuncReturn(_)
+            )
+        })
+    }
+
+    // Stop running the closest LOCK. If "go_back" is true, the lock will start over.
+    // Every IF inside of that LOCK is marked as done, so nothing else will run until ENDLOCK.
+    fn leave_lock(&mut self, go_back: bool) -> Result<(), String> {
+        let Some(lock_idx) = self.list.iter().rposition(|x| *x == ShellMode::Lock) else {
+            return Err("Usage of \"FREE\" or \"CONTINUE\" is not permited outside of the \"LOCK\" or \"FOR\" statement".to_string());
+        };
+        self.list[lock_idx] = if go_back {
+            ShellMode::LockContinue
+        } else {
+            ShellMode::LockFree
+        };
+        for mode in self.list.iter_mut().skip(lock_idx + 1) {
+            *mode = ShellMode::IfDone;
+        }
+        Ok(())
+    }
+
+    // Stop running the function. Every LOCK inside of it is freed.
+    fn leave_function(&mut self, code: i32) {
+        let Some(func_idx) = self.list.iter().rposition(|x| *x == ShellMode::Func) else {
+            return;
+        };
+        self.list[func_idx] = ShellMode::FuncReturn(code);
+        for mode in self.list.iter_mut().skip(func_idx + 1) {
+            *mode = match mode {
+                ShellMode::Lock | ShellMode::LockContinue => ShellMode::LockFree,
+                _ => ShellMode::IfDone,
+            };
+        }
+    }
+}
+
+fn run_script(script: Vec<Node>) {
+    let mut modes = ShellModes::new();
+    run_block(&script, &mut modes);
+}
+
+/*
+Run commands from "$(...)" and give back everything they printed.
+New lines at the end of the output are removed.
+*/
+fn substitute(script: &str) -> Result<String, String> {
+    let ast = lexer::tokenize(script)
+        .and_then(parser::parse)
+        .map_err(|e| format!("Wrong command substitution \"$({script})\": {e}"))?;
+    let mut errors = Vec::new();
+    find_errors(&ast, false, false, &mut errors);
+    if !errors.is_empty() {
+        return Err(format!(
+            "Wrong command substitution \"$({script})\": {}",
+            errors.join(", ")
+        ));
+    }
+
+    // Errors should still point to the line of the command that uses the substitution
+    let line = index();
+    let output = exec::capture(|| run_script(ast))?;
+    set_index(line);
+    if interrupt_now() {
+        return Err(format!("Command substitution \"$({script})\" was interrupted"));
+    }
+    Ok(output.trim_end_matches('\n').to_string())
+}
+
+fn run_block(block: &[Node], modes: &mut ShellModes) {
+    for node in block {
+        // Stop everything if CTRL+C was pressed, some command failed
+        // or if FREE/CONTINUE was used
+        if interrupt_now() || modes.skipping() {
+            return;
+        }
+        set_index(node.line());
+
+        match node {
+            Node::Pipeline(pipeline) => run_pipeline(pipeline, true),
+            Node::Chain { first, rest, .. } => {
+                // Failures of commands other than the last one don't stop the script
+                run_pipeline(first, false);
+                for (idx, (kind, pipeline)) in rest.iter().enumerate() {
+                    if interrupt_now() {
+                        break;
+                    }
+                    let success = last_exit_code() == 0;
+                    match kind {
+                        ChainKind::And if !success => continue,
+                        ChainKind::Or if success => continue,
+                        _ => run_pipeline(pipeline, idx == rest.len() - 1),
+                    }
+                }
+            }
+            Node::If {
+                branches,
+                otherwise,
+                line,
+            } => {
+                // Find the first branch with a comparison that is true
+                let mut body = otherwise.as_ref();
+                for (condition, branch) in branches {
+                    let mut buf = vec![String::from("if")];
+                    buf.extend(condition.iter().map(|w| w.text.clone()));
+                    match r#if::logic(buf) {
+                        Ok(true) => {
+                            body = Some(branch);
+                            break;
+                        }
+                        Ok(false) => (),
+                        Err(e) => {
+                            print_err(e.as_str(), "IF", *line);
+                            return;
+                        }
+                    }
+                }
+                if let Some(body) = body {
+                    modes.list.push(ShellMode::CmpSuccess);
+                    run_block(body, modes);
+                    modes.list.pop();
+                }
+            }
+            Node::Lock { body, .. } => loop {
+                modes.list.push(ShellMode::Lock);
+                run_block(body, modes);
+                if modes.list.pop() == Some(ShellMode::LockFree) || interrupt_now() {
+                    break;
+                }
+            },
+            Node::For {
+                switches,
+                variable,
+                method,
+                list,
+                body,
+                line,
+            } => {
+                if let Some(s) = switches.first() {
+                    print_err(format!("Unknown switch: {}", s.text).as_str(), "FOR", *line);
+                    return;
+                }
+                if let Err(e) = variables::check_name(&variable.text) {
+                    print_err(e.as_str(), "FOR", *line);
+                    return;
+                }
+                let items = match for_items(*method, list) {
+                    Ok(i) => i,
+                    Err(e) => {
+                        print_err(e.as_str(), "FOR", *line);
+                        return;
+                    }
+                };
+                // FOR works just like LOCK, so FREE and CONTINUE can be used inside of it
+                for item in items {
+                    variables::set(&variable.text, item);
+                    modes.list.push(ShellMode::Lock);
+                    run_block(body, modes);
+                    if modes.list.pop() == Some(ShellMode::LockFree) || interrupt_now() {
+                        break;
+                    }
+                }
+            }
+            Node::Free(line) | Node::Continue(line) => {
+                if let Err(e) = modes.leave_lock(matches!(node, Node::Continue(_))) {
+                    print_err(e.as_str(), "LOCK", *line);
+                }
+            }
+            Node::Func { name, body, .. } => functions::define(&name.text, body.clone()),
+            Node::Return { code, line } => {
+                let code = match code {
+                    Some(w) => match escape_slashes(&w.text, true, true)
+                        .and_then(|x| x.parse::<i32>().map_err(|_| format!("Exit code must be a number: {x}")))
+                    {
+                        Ok(c) => c,
+                        Err(e) => {
+                            print_err(e.as_str(), "RETURN", *line);
+                            return;
+                        }
+                    },
+                    None => 0,
+                };
+                modes.leave_function(code);
+            }
+        }
+    }
+}
+
+/*
+Split things after the separation method into elements that FOR will iterate through.
+for a persign hello do ...              -- h, e, l, l, o
+for a perword "hello world" do ...      -- hello, world
+for a perline $TEXT do ...              -- every line of a variable
+for a in one "two three" $LIST do ...   -- one, two three and every element of a list
+for a perline OUT:p file.txt do ...     -- every line printed by a command
+*/
+fn for_items(method: parser::ForMethod, list: &[parser::Word]) -> Result<Vec<String>, String> {
+    let mut args = expand_words(list)?;
+    // Empty list or a command that printed nothing. There is nothing to iterate through.
+    if args.is_empty() || list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Only an unquoted "OUT:" runs a command, so "$VAR" can't start anything by accident
+    let text = match args[0].strip_prefix("OUT:").filter(|_| list[0].text.starts_with("OUT:")) {
+        Some(cmd) => {
+            args[0] = cmd.to_string();
+            if args[0].is_empty() {
+                return Err("Missing a command after \"OUT:\"".to_string());
+            }
+            exec::output(&args)?
+        }
+        None if method == parser::ForMethod::In => return Ok(args),
+        // Elements of lists are treated like separate lines
+        None if method == parser::ForMethod::PerLine => args.join("\n"),
+        None => args.join(" "),
+    };
+
+    Ok(match method {
+        parser::ForMethod::PerSign => text.chars().map(String::from).collect(),
+        parser::ForMethod::PerWord | parser::ForMethod::In => {
+            text.split_whitespace().map(String::from).collect()
+        }
+        parser::ForMethod::PerLine => text.lines().map(String::from).collect(),
+    })
+}
+
+/*
+Run a function defined with FUNC. Returns None when there is no function with such name.
+Function gets it's own scope of variables with arguments in "$1", "$2", etc.
+All of the arguments are also available as a list in "$@".
+*/
+fn call_function(args: &[String]) -> Option<i32> {
+    let body = functions::get(&args[0])?;
+    if let Err(e) = functions::enter() {
+        print_err(e, args[0].clone(), index());
+        return Some(1);
+    }
+
+    variables::push_scope();
+    for (idx, a) in args.iter().enumerate() {
+        variables::set_local(&idx.to_string(), variables::Value::Text(a.clone()));
+    }
+    variables::set_local("@", variables::Value::List(args[1..].to_vec()));
+
+    let mut modes = ShellModes::new();
+    modes.list.push(ShellMode::Func);
+    run_block(&body, &mut modes);
+
+    variables::pop_scope();
+    functions::leave();
+
+    match modes.list.first() {
+        Some(ShellMode::FuncReturn(code)) => Some(*code),
+        _ => Some(0),
+    }
+}
+
+// Commands that are handled by the shell itself instead of a separate program
+const BUILTINS: [&str; 22] = [
+    "gt", "pushd", "popd", "dirs", "getenv", "setenv", "remenv", "export", "local", "append",
+    "remove", "++", "--", "alias", "unalias", "history", "jobs", "fg", "bg", "wait", "kill", "set",
+];
+
+/*
+Get rid of quotation marks and escaping slashes. Replace variable names with their contents.
+Braces and tildes are expanded first. Unquoted wildcards are replaced with names of matching files.
+*/
+fn expand_words(words: &[parser::Word]) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for w in words {
+        for text in expansion::braces(&w.text)? {
+            expand_word(&expansion::tilde(&text), &mut args, true)?;
+        }
+    }
+    Ok(args)
+}
+
+// Same as above, but "[", "]", "{" and "}" of a list or a dictionary are not treated as wildcards or braces
+fn expand_collection_literal(words: &[parser::Word]) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for w in words {
+        expand_word(&expansion::tilde(&w.text), &mut args, false)?;
+    }
+    Ok(args)
+}
+
+fn expand_word(text: &str, args: &mut Vec<String>, globs: bool) -> Result<(), String> {
+    // Unquoted reference to a whole list or dictionary gives every element as a separate argument
+    if let Some(value) = whole_variable(text).and_then(variables::get_value)
+        && !matches!(value, variables::Value::Text(_))
+    {
+        args.extend(value.elements());
+        return Ok(());
+    }
+    // Output of an unquoted substitution is split into words: for x perword $(ls) do ...
+    if let Some(script) = global::whole_substitution(text) {
+        args.extend(substitute(&script)?.split_whitespace().map(String::from));
+        return Ok(());
+    }
+    if !globs {
+        args.push(escape_slashes(text, true, true)?);
+        return Ok(());
+    }
+    let pattern = global::glob_pattern(text)?;
+    if glob::is_pattern(&pattern) {
+        let files = glob::expand(&pattern);
+        if !files.is_empty() {
+            args.extend(files);
+            return Ok(());
+        }
+        match unmatched_globs() {
+            UnmatchedGlobs::Error => return Err(format!("No files match \"{}\"", glob::unescape(&pattern))),
+            UnmatchedGlobs::Empty => return Ok(()),
+            UnmatchedGlobs::Keep => (),
+        }
+    }
+    args.push(glob::unescape(&pattern));
+    Ok(())
 }
 
-fn run_script(script: Vec<String>) {
-    // When looking for words for a command that we are starting to work with,
-    // where should we start searching from?
-    let mut start_search_from = 0;
-    
-    // Create a buffer that will hold words from the script **that we are currently working on**.
-    let buf = script[start_search_from..].iter().position(
-        |x| !x.ends_with("\\;") && (x.ends_with(';') || x.ends_with('\n'))
-    );
-    start_search_from += buf.len();
-    
+// Get a variable name if the word is just "$NAME" or "${NAME}"
+fn whole_variable(word: &str) -> Option<&str> {
+    let name = word.strip_prefix('$')?;
+    let name = name
+        .strip_prefix('{')
+        .and_then(|x| x.strip_suffix('}'))
+        .unwrap_or(name);
+    // "$@" holds all arguments of a function
+    if name == "@" || (!name.is_empty() && name.chars().all(|x| x.is_alphanumeric() || x == '_')) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+// Lists and dictionaries are only created when the bracket is not enquoted: setenv LIST [ one two ]
+fn is_collection_literal(words: &[parser::Word]) -> bool {
+    words.len() > 2
+        && words[0].text == "setenv"
+        && (words[2].text.starts_with('[') || words[2].text.starts_with('{'))
+}
+
+/*
+Run shell's builtin command or execute programs connected with pipes.
+When "set -e" was used and "check_errors" is true, failed pipeline stops the script.
+*/
+fn run_pipeline(pipeline: &parser::Pipeline, check_errors: bool) {
+    let mut stages = Vec::new();
+    // Whether the first command creates a list or a dictionary is decided from words as they were typed
+    let collection = is_collection_literal(&pipeline.commands[0].words);
+    for cmd in &pipeline.commands {
+        let mut stage = exec::Stage {
+            args: Vec::new(),
+            redirections: Vec::new(),
+        };
+        let expanded = if is_collection_literal(&cmd.words) {
+            expand_collection_literal(&cmd.words)
+        } else {
+            expand_words(&cmd.words).and_then(aliases::expand)
+        };
+        let expanded = expanded.and_then(|args| {
+            stage.args = args;
+            for r in &cmd.redirections {
+                let target = match &r.target {
+                    Some(w) => Some(escape_slashes(&w.text, true, true)?),
+                    None => None,
+                };
+                stage.redirections.push((r.kind, target));
+            }
+            Ok(())
+        });
+        if let Err(e) = expanded {
+            print_err(e, cmd.words[0].text.clone(), cmd.line);
+            return;
+        }
+        stages.push(stage);
+    }
+
+    // Words can expand to nothing: "$(true)" or an empty list. Such command does nothing and succeeds.
+    if let Some(empty) = stages.iter().position(|x| x.args.is_empty()) {
+        if stages.len() > 1 {
+            set_last_exit_code(1);
+            print_err(
+                "Command expanded to nothing, so it can't be a part of a pipeline".to_string(),
+                pipeline.commands[empty].words[0].text.clone(),
+                pipeline.line,
+            );
+        } else {
+            finish_pipeline(0, "", pipeline.line, check_errors);
+        }
+        return;
+    }
+
+    // Builtins and functions are run by the shell itself,
+    // so they can't read or write anything through pipes and files or run in the background
+    if stages.len() > 1 || !stages[0].redirections.is_empty() || pipeline.background {
+        if let Some(stage) = stages.iter().find(|x| {
+            BUILTINS.contains(&x.args[0].as_str()) || functions::get(&x.args[0]).is_some()
+        }) {
+            print_err(
+                "Builtin commands and functions can't be used in a pipeline, with redirections or in the background".to_string(),
+                stage.args[0].clone(),
+                pipeline.line,
+            );
+            return;
+        }
+        let name = stages[0].args[0].clone();
+        match exec::pipeline(stages, pipeline.background) {
+            Ok(code) => finish_pipeline(code.unwrap_or(1), &name, pipeline.line, check_errors),
+            Err(e) => {
+                set_last_exit_code(1);
+                print_err(e, name, pipeline.line);
+            }
+        }
+        return;
+    }
+
+    let args = &stages[0].args;
+    let result = match run_builtin(args, collection) {
+        Some(r) => r.map(Some),
+        None => match call_function(args) {
+            Some(code) => Ok(Some(code)),
+            None => exec::exec(args),
+        },
+    };
+    match result {
+        Ok(code) => finish_pipeline(code.unwrap_or(1), &args[0], pipeline.line, check_errors),
+        Err(e) => {
+            set_last_exit_code(1);
+            print_err(e, args[0].clone(), pipeline.line);
+        }
+    }
+}
+
+fn finish_pipeline(code: i32, name: &str, line: usize, check_errors: bool) {
+    set_last_exit_code(code);
+    if code != 0 && check_errors && errexit() {
+        print_err(format!("Command failed with exit code {code}"), name.to_string(), line);
+    }
+}
+
+/*
+set -e - Stop the script when any command fails
+set +e - Don't do that anymore
+set    - Show which options are enabled
+*/
+fn set(buf: &[String]) -> Result<(), String> {
+    if buf.len() == 1 {
+        println!("{}e", if errexit() { "-" } else { "+" });
+        return Ok(());
+    }
+    for option in &buf[1..] {
+        match option.as_str() {
+            "-e" => set_errexit(true),
+            "+e" => set_errexit(false),
+            _ => return Err(format!("Unknown option: {option}")),
+        }
+    }
+    Ok(())
+}
+
+// Run a command that is a part of the shell. Returns None if it's not a builtin.
+fn run_builtin(args: &[String], collection: bool) -> Option<Result<i32, String>> {
+    let result = match args[0].as_str() {
+        "gt" => directories::gt(args),
+        "pushd" => directories::pushd(args),
+        "popd" => directories::popd(args),
+        "dirs" => directories::dirs(args),
+        "getenv" => variables::getenv(args).map(|x| println!("{x}")),
+        "setenv" if collection => variables::setenv_collection(args),
+        "setenv" => variables::setenv(args),
+        "remenv" => variables::remenv(args),
+        "export" => variables::export(args),
+        "local" => variables::local(args),
+        "append" => variables::append(args),
+        "remove" => variables::remove_elements(args),
+        "++" => variables::chenv(args, true),
+        "--" => variables::chenv(args, false),
+        "alias" => aliases::alias(args),
+        "unalias" => aliases::unalias(args),
+        "history" => history::builtin(args),
+        "set" => set(args),
+        "jobs" => jobs::list(args),
+        "bg" => jobs::bg(args),
+        "kill" => jobs::kill(args),
+        // These return exit codes of jobs
+        "fg" => return Some(jobs::fg(args)),
+        "wait" => return Some(jobs::wait(args)),
+        _ => return None,
+    };
+    // Builtins return 0 when they succeed and 1 when they fail
+    Some(result.map(|_| 0))
 }
@@ -1,26 +1,263 @@
+use crate::functions;
+use crate::global::last_exit_code;
 use carrot_libs::system;
-use std::env::var_os;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::{Mutex, MutexGuard};
+
+/*
+Variables are stored by the shell itself instead of the process environment.
+Only variables marked with "export" are passed to programs started by the shell.
+
+Every script has it's own scope of variables. Functions get another scope, when they are called.
+Variables are looked up from the innermost scope to the global one.
+New variables are created in the scope of the script, even inside of a function.
+Only "local" creates a variable that disappears when the function returns.
+The global scope contains everything that was in the environment when the shell started.
+
+Variable can hold a text, a list or a dictionary:
+setenv TEXT hello world
+setenv LIST [ one two 'three and four' ]
+setenv DICT { name=John 'full name=John Smith' }
+
+Elements are available with "$LIST[0]" or "$DICT[name]" and "${#LIST}" returns the number of them.
+
+"$?" and "$STATUS" always hold the exit code of the last command. They can't be changed.
+*/
+#[derive(Clone)]
+pub enum Value {
+    Text(String),
+    List(Vec<String>),
+    Dict(BTreeMap<String, String>),
+}
+
+impl Value {
+    // Contents of the variable when it's used inside of a text
+    pub fn to_text(&self) -> String {
+        match self {
+            Value::Text(t) => t.clone(),
+            Value::List(_) | Value::Dict(_) => self.elements().join(" "),
+        }
+    }
+
+    // Elements of a list or values of a dictionary. Text is just one element.
+    pub fn elements(&self) -> Vec<String> {
+        match self {
+            Value::Text(t) => vec![t.clone()],
+            Value::List(l) => l.clone(),
+            Value::Dict(d) => d.values().cloned().collect(),
+        }
+    }
+
+    // The same syntax that was used to create the variable
+    fn to_literal(&self) -> String {
+        match self {
+            Value::Text(t) => t.clone(),
+            Value::List(l) => format!("[ {} ]", l.iter().map(|x| quote(x)).collect::<Vec<String>>().join(" ")),
+            Value::Dict(d) => format!(
+                "{{ {} }}",
+                d.iter()
+                    .map(|(k, v)| quote(&format!("{k}={v}")))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+// Put text with spaces in quotation marks
+fn quote(text: &str) -> String {
+    if text.contains(char::is_whitespace) || text.is_empty() {
+        format!("'{}'", text.replace('\'', "\\'"))
+    } else {
+        text.to_string()
+    }
+}
+
+struct Variable {
+    value: Value,
+    exported: bool,
+}
+
+static SCOPES: Mutex<Vec<HashMap<String, Variable>>> = Mutex::new(Vec::new());
+
+fn scopes() -> MutexGuard<'static, Vec<HashMap<String, Variable>>> {
+    let mut scopes = SCOPES.lock().unwrap_or_else(|e| e.into_inner());
+    // Make sure that the global scope is always there
+    if scopes.is_empty() {
+        scopes.push(HashMap::new());
+    }
+    scopes
+}
+
+// Copy variables from the environment to the global scope
+pub fn init() {
+    let mut scopes = scopes();
+    for (name, value) in env::vars() {
+        scopes[0].insert(
+            name,
+            Variable {
+                value: Value::Text(value),
+                exported: true,
+            },
+        );
+    }
+}
+
+pub fn push_scope() {
+    scopes().push(HashMap::new());
+}
+
+pub fn pop_scope() {
+    let mut scopes = scopes();
+    if scopes.len() > 1 {
+        scopes.pop();
+    }
+}
+
+// Variables that are set by the shell itself
+const READ_ONLY: [&str; 2] = ["?", "STATUS"];
+
+pub fn get_value(name: &str) -> Option<Value> {
+    if READ_ONLY.contains(&name) {
+        return Some(Value::Text(last_exit_code().to_string()));
+    }
+    scopes()
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(name))
+        .map(|v| v.value.clone())
+}
+
+// Get contents of a variable as a text.
+// Reference can also point to an element of a list or a dictionary: "LIST[0]", "DICT[key]"
+pub fn get(reference: &str) -> Option<String> {
+    let (name, index) = match reference.split_once('[') {
+        Some((name, rest)) => match rest.strip_suffix(']') {
+            Some(index) => (name, Some(index)),
+            None => (reference, None),
+        },
+        None => (reference, None),
+    };
+
+    match (get_value(name)?, index) {
+        (value, None) => Some(value.to_text()),
+        (Value::List(l), Some(index)) => l.get(index.parse::<usize>().ok()?).cloned(),
+        (Value::Dict(d), Some(key)) => d.get(key).cloned(),
+        (Value::Text(_), Some(_)) => None,
+    }
+}
+
+// Number of elements in a list or a dictionary or number of letters in a text
+pub fn length(name: &str) -> Option<usize> {
+    match get_value(name)? {
+        Value::Text(t) => Some(t.chars().count()),
+        Value::List(l) => Some(l.len()),
+        Value::Dict(d) => Some(d.len()),
+    }
+}
+
+// Change a variable in the scope where it is defined or create it in the scope of the script
+pub fn set(name: &str, value: String) {
+    set_value(name, Value::Text(value));
+}
+
+pub fn set_value(name: &str, value: Value) {
+    let mut scopes = scopes();
+    if let Some(variable) = scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+        variable.value = value;
+    } else {
+        // Functions are always called by the script, so their scopes are on the top of it's scope
+        let script = scopes.len().saturating_sub(functions::depth() + 1);
+        scopes[script].insert(
+            name.to_string(),
+            Variable {
+                value,
+                exported: false,
+            },
+        );
+    }
+}
+
+// Set a variable and make it visible for programs at once
+pub fn set_exported(name: &str, value: String) {
+    set(name, value);
+    if let Some(variable) = scopes().iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+        variable.exported = true;
+    }
+}
+
+// Create a variable in the current scope even if some outer scope already has it
+pub fn set_local(name: &str, value: Value) {
+    scopes().last_mut().unwrap().insert(
+        name.to_string(),
+        Variable {
+            value,
+            exported: false,
+        },
+    );
+}
+
+// Returns false if there was no such variable
+pub fn remove(name: &str) -> bool {
+    scopes()
+        .iter_mut()
+        .rev()
+        .find_map(|scope| scope.remove(name))
+        .is_some()
+}
+
+// Names of all variables that can be used right now
+pub fn names() -> Vec<String> {
+    let mut list: Vec<String> = scopes()
+        .iter()
+        .flat_map(|scope| scope.keys().cloned())
+        .collect();
+    list.sort();
+    list.dedup();
+    list
+}
+
+// Variables that should be visible in the environment of programs started by the shell
+pub fn exported() -> Vec<(String, String)> {
+    let mut list = HashMap::new();
+    // Variables from inner scopes hide the ones with the same name from outer scopes
+    for scope in scopes().iter() {
+        for (name, variable) in scope {
+            if variable.exported {
+                list.insert(name.clone(), variable.value.to_text());
+            } else {
+                list.remove(name);
+            }
+        }
+    }
+    list.into_iter().collect()
+}
+
+// Make sure that the variable can be changed by the user
+pub fn check_name(name: &str) -> Result<(), String> {
+    if READ_ONLY.contains(&name) {
+        return Err(format!("Variable \"{name}\" is read-only!"));
+    }
+    if system::check_simple_characters_compliance(name).is_err() {
+        return Err(format!("Variable name contains invalid characters: {name}!"));
+    }
+    Ok(())
+}
 
 pub fn getenv(buf: &[String]) -> Result<String, String> {
     // Check if there is just ONE argument
     // We can't check more than one variable at the same time
     match buf.len() {
         1 => Err(("Give me a variable name to check!").to_string()),
-        2 => match var_os(&buf[1]) {
-            Some(ret) => match ret.into_string() {
-                Ok(a) => Ok(a),
-                Err(_) => Err(format!(
-                    "Error occured while setting up a variable: {}!",
-                    buf[1]
-                )),
-            },
+        2 => match get_value(&buf[1]) {
+            Some(ret) => Ok(ret.to_literal()),
             None => Err(format!("Variable \"{}\" is not set!", buf[1])),
         },
         _ => Err(("Cannot check multiple variables simultaneously!").to_string()),
     }
 }
 
-use std::env::set_var;
 pub fn setenv(buf: &[String]) -> Result<(), String> {
     // Check if there is just ONE argument
     // We can't set more than one variable at the same time
@@ -28,12 +265,7 @@ pub fn setenv(buf: &[String]) -> Result<(), String> {
         Err(("Give me a variable name and it's contents to set!").to_string())
     } else {
         // Allow user to set variables with proper letters only
-        if system::check_simple_characters_compliance(&buf[1]).is_err() {
-            return Err(format!(
-                "Variable name contains invalid characters: {}!",
-                buf[1]
-            ));
-        }
+        check_name(&buf[1])?;
         // Value must contain contents of arg 2+
         let mut value = String::new();
         for a in &buf[2..] {
@@ -41,14 +273,11 @@ pub fn setenv(buf: &[String]) -> Result<(), String> {
         }
 
         // trim _end() is going to remove any trailing white characters at the end
-        unsafe {
-            set_var(&buf[1], value.trim_end());
-        }
+        set(&buf[1], value.trim_end().to_string());
         Ok(())
     }
 }
 
-use std::env::remove_var;
 pub fn remenv(buf: &[String]) -> Result<(), String> {
     // Check if there is just ONE argument
     // We can't set more than one variable at the same time
@@ -56,21 +285,53 @@ pub fn remenv(buf: &[String]) -> Result<(), String> {
         Err(("Give me a variable name and it's contents to set!").to_string())
     } else {
         // Allow user to remove variables with proper letters only
-        if system::check_simple_characters_compliance(&buf[1]).is_err() {
-            return Err(format!(
-                "Variable name contains invalid characters: {}!",
-                buf[1]
-            ));
-        }
-        unsafe {
-            remove_var(&buf[1]);
+        check_name(&buf[1])?;
+        if !remove(&buf[1]) {
+            return Err(format!("Variable \"{}\" is not set!", buf[1]));
         }
         Ok(())
     }
 }
 
+// Create a variable that is only visible inside of the function that is running
+pub fn local(buf: &[String]) -> Result<(), String> {
+    if buf.len() < 2 {
+        return Err(("Give me a variable name and it's contents to set!").to_string());
+    }
+    check_name(&buf[1])?;
+    set_local(&buf[1], Value::Text(buf[2..].join(" ")));
+    Ok(())
+}
+
+// Make a variable visible for programs started by the shell
+// Optionally, set it's contents at the same time
+pub fn export(buf: &[String]) -> Result<(), String> {
+    if buf.len() < 2 {
+        return Err(("Give me a variable name to export!").to_string());
+    }
+    if buf.len() > 2 {
+        setenv(buf)?;
+    }
+
+    let mut scopes = scopes();
+    match scopes
+        .iter_mut()
+        .rev()
+        .find_map(|scope| scope.get_mut(&buf[1]))
+    {
+        Some(variable) => {
+            variable.exported = true;
+            Ok(())
+        }
+        None => Err(format!("Variable \"{}\" is not set!", buf[1])),
+    }
+}
+
 pub fn chenv(buf: &[String], increment: bool) -> Result<(), String> {
-    let mut set = 1;
+    if let Some(name) = buf.get(1) {
+        check_name(name)?;
+    }
+    let mut set_by = 1;
     match buf.len() {
         1 => Err(("Give me a variable name to increment!").to_string()),
         2 | 3 => {
@@ -82,40 +343,32 @@ pub fn chenv(buf: &[String], increment: bool) -> Result<(), String> {
                             e.kind()
                         ))
                     }
-                    Ok(a) => set = a,
+                    Ok(a) => set_by = a,
                 }
             }
             // Read variable
-            if let Some(ret) = var_os(&buf[1]) {
-                // Convert to a string from OsString
-                match ret.into_string() {
+            if let Some(Value::Text(ret)) = get_value(&buf[1]) {
+                // Convert it to a number
+                match ret.parse::<isize>() {
+                    Ok(a) => {
+                        // Increment/decrement and set it up
+                        if increment {
+                            set(&buf[1], (a + set_by).to_string());
+                        } else {
+                            set(&buf[1], (a - set_by).to_string());
+                        }
+                        Ok(())
+                    }
                     Err(_) => Err(format!(
-                        "Error occured while checking a variable: {}!",
+                        "Error occured while converting a variable to a number: {}!",
                         buf[1]
                     )),
-                    Ok(ret) => {
-                        // Convert it to a number
-                        match ret.parse::<isize>() {
-                            Ok(a) => {
-                                // Increment/decrement and set it up
-                                if increment {
-                                    unsafe {
-                                        set_var(&buf[1], (a + set).to_string());
-                                    }
-                                } else {
-                                    unsafe {
-                                        set_var(&buf[1], (a - set).to_string());
-                                    }
-                                }
-                                Ok(())
-                            }
-                            Err(_) => Err(format!(
-                                "Error occured while converting a variable to a number: {}!",
-                                buf[1]
-                            )),
-                        }
-                    }
                 }
+            } else if get_value(&buf[1]).is_some() {
+                Err(format!(
+                    "Lists and dictionaries can't be converted to a number: {}!",
+                    buf[1]
+                ))
             } else {
                 Err(format!("Variable \"{}\" is not set!", buf[1]))
             }
@@ -123,3 +376,114 @@ pub fn chenv(buf: &[String], increment: bool) -> Result<(), String> {
         _ => Err(("Cannot understand more arguments!").to_string()),
     }
 }
+
+/*
+Create a list or a dictionary from words enclosed in brackets
+setenv LIST [ one two three ]
+setenv DICT { one=1 two=2 }
+Brackets can be glued to the first and last element, so "[one two three]" also works.
+*/
+pub fn setenv_collection(buf: &[String]) -> Result<(), String> {
+    if buf.len() < 3 {
+        return Err("Give me a variable name and a list or a dictionary!".to_string());
+    }
+    check_name(&buf[1])?;
+
+    let mut words = buf[2..].to_vec();
+    let (opening, closing) = if words[0].starts_with('[') { ('[', ']') } else { ('{', '}') };
+    words[0].remove(0);
+    match words.last_mut().and_then(|w| w.pop()) {
+        Some(c) if c == closing => (),
+        _ => return Err(format!("Missing \"{closing}\" at the end of \"{opening}\"")),
+    }
+    // Brackets separated with spaces leave empty words behind
+    if words.first().is_some_and(|w| w.is_empty()) {
+        words.remove(0);
+    }
+    if words.last().is_some_and(|w| w.is_empty()) {
+        words.pop();
+    }
+
+    if opening == '[' {
+        set_value(&buf[1], Value::List(words));
+    } else {
+        let mut dict = BTreeMap::new();
+        for w in words {
+            match w.split_once('=') {
+                Some((key, value)) => dict.insert(key.to_string(), value.to_string()),
+                None => return Err(format!("Missing \"=\" between a key and a value: {w}")),
+            };
+        }
+        set_value(&buf[1], Value::Dict(dict));
+    }
+    Ok(())
+}
+
+// Add elements to the end of a list or add keys to a dictionary
+// append LIST four five
+// append DICT three=3
+pub fn append(buf: &[String]) -> Result<(), String> {
+    if buf.len() < 3 {
+        return Err(("Give me a variable name and elements to append!").to_string());
+    }
+    match get_value(&buf[1]) {
+        Some(Value::List(mut l)) => {
+            l.extend_from_slice(&buf[2..]);
+            set_value(&buf[1], Value::List(l));
+        }
+        Some(Value::Dict(mut d)) => {
+            for w in &buf[2..] {
+                match w.split_once('=') {
+                    Some((key, value)) => d.insert(key.to_string(), value.to_string()),
+                    None => return Err(format!("Missing \"=\" between a key and a value: {w}")),
+                };
+            }
+            set_value(&buf[1], Value::Dict(d));
+        }
+        Some(Value::Text(_)) => {
+            return Err(format!("Variable \"{}\" is not a list nor a dictionary!", buf[1]));
+        }
+        None => return Err(format!("Variable \"{}\" is not set!", buf[1])),
+    }
+    Ok(())
+}
+
+// Remove elements from a list by their positions or remove keys from a dictionary
+// remove LIST 0
+// remove DICT three
+pub fn remove_elements(buf: &[String]) -> Result<(), String> {
+    if buf.len() < 3 {
+        return Err(("Give me a variable name and elements to remove!").to_string());
+    }
+    match get_value(&buf[1]) {
+        Some(Value::List(mut l)) => {
+            let mut positions = Vec::new();
+            for w in &buf[2..] {
+                match w.parse::<usize>() {
+                    Ok(p) if p < l.len() => positions.push(p),
+                    _ => return Err(format!("There is no element at position \"{w}\" in \"{}\"!", buf[1])),
+                }
+            }
+            // Start from the end, so positions of other elements do not change
+            positions.sort_unstable();
+            positions.dedup();
+            for p in positions.into_iter().rev() {
+                l.remove(p);
+            }
+            set_value(&buf[1], Value::List(l));
+        }
+        Some(Value::Dict(mut d)) => {
+            for w in &buf[2..] {
+                if d.remove(w).is_none() {
+                    return Err(format!("There is no key \"{w}\" in \"{}\"!", buf[1]));
+                }
+            }
+            set_value(&buf[1], Value::Dict(d));
+        }
+        Some(Value::Text(_)) => {
+            return Err(format!("Variable \"{}\" is not a list nor a dictionary!", buf[1]));
+        }
+        None => return Err(format!("Variable \"{}\" is not set!", buf[1])),
+    }
+    Ok(())
+}